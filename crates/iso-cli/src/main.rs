@@ -1,10 +1,31 @@
-use clap::Parser;
-use iso9660_rs::{file::FileInput, ElToritoOptions, FormatOptions};
-use std::{fs::OpenOptions, io::Write, path::PathBuf};
+use clap::{Parser, Subcommand};
+use iso9660_rs::{
+    ElToritoOptions, FormatOptions, error::IsoError, file::FileInput, reader::IsoReader,
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 #[derive(Parser)]
 pub struct Args {
-    input: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Writes an image of the bundled `isoroot` directory, and reads it back
+    Test { input: PathBuf },
+    /// Extracts the contents of an image into a directory
+    Extract {
+        image: PathBuf,
+        output: PathBuf,
+        /// The path in the image to extract
+        #[arg(long, default_value = "/")]
+        path: String,
+    },
 }
 
 fn main() {
@@ -14,8 +35,27 @@ fn main() {
         .init()
         .unwrap();
 
-    write(&args.input);
-    read(&args.input);
+    match args.command {
+        Command::Test { input } => {
+            write(&input);
+            read(&input);
+        }
+        Command::Extract {
+            image,
+            output,
+            path,
+        } => {
+            if let Err(err) = extract(&image, &output, &path) {
+                eprintln!(
+                    "error: failed to extract {} from {}: {}",
+                    path,
+                    image.display(),
+                    err
+                );
+                std::process::exit(1);
+            }
+        }
+    }
 }
 
 fn write(file: &PathBuf) {
@@ -23,6 +63,7 @@ fn write(file: &PathBuf) {
         .read(true)
         .write(true)
         .create(true)
+        .truncate(true)
         .open(file)
         .unwrap();
     // We zero the file out to make sure we don't have any old data
@@ -32,13 +73,15 @@ fn write(file: &PathBuf) {
     iso9660_rs::IsoImage::format_new(
         &mut file,
         FormatOptions {
-            files: FileInput::from_fs(concat!(env!("CARGO_MANIFEST_DIR"), "/isoroot").into()).unwrap(),
+            files: FileInput::from_fs(concat!(env!("CARGO_MANIFEST_DIR"), "/isoroot").into())
+                .unwrap(),
             protective_mbr: true,
+            partitions: Vec::new(),
             el_torito: Some(ElToritoOptions {
                 load_size: 4,
                 boot_image_path: "limine-bios-cd.bin".to_string(),
                 boot_info_table: true,
-            })
+            }),
         },
     )
     .unwrap();
@@ -46,9 +89,33 @@ fn write(file: &PathBuf) {
 }
 
 fn read(file: &PathBuf) {
-    let mut file = OpenOptions::new().read(true).open(file).unwrap();
-    let mut iso = iso9660_rs::IsoImage::new(&mut file).unwrap();
-    let mut root_dir = iso.root_directory();
+    let file = File::open(file).unwrap();
+    let mut iso = IsoReader::new(file).unwrap();
+    log::debug!("Boot catalogue: {:#?}", iso.boot_catalogue());
+    log::debug!("Root Directory: {:#?}", iso.root_directory().entries());
     //println!("Root Directory: {:#?}", root_dir.entries());
     //println!("Path table: {:#?}", iso.path_table().entries());
 }
+
+fn extract(image: &Path, output: &Path, path: &str) -> Result<(), IsoError> {
+    let file = File::open(image)?;
+    let mut iso = IsoReader::new(file)?;
+    let report = iso.extract(path, output)?;
+    for skipped in &report.skipped {
+        println!("skipped {}: {}", skipped.path, skipped.reason);
+    }
+    for renamed in &report.renamed {
+        println!(
+            "renamed {} to {}",
+            renamed.path,
+            renamed.host_path.display()
+        );
+    }
+    println!(
+        "extracted {} files and {} directories to {}",
+        report.files,
+        report.directories,
+        output.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,527 @@
+use std::{
+    io::SeekFrom,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, ReadBuf};
+
+use crate::{
+    PathTableRef,
+    boot::BootCatalogue,
+    directory::{
+        self, DirectoryRecord, DirectoryRecordHeader, DirectoryRef, Interleave, Metadata,
+        keep_match,
+    },
+    error::IsoError,
+    path::PathTableEntryHeader,
+    slice::{PathTableEntryRef, SliceDirectoryEntries},
+    split_path,
+    types::{Endian, IsoStringFile},
+    volume::VolumeDescriptorList,
+};
+
+/// A read-only ISO image over tokio's [`AsyncRead`] and [`AsyncSeek`], the async counterpart of
+/// [`crate::reader::IsoReader`].
+///
+/// Sectors are read asynchronously, and then parsed with the same code as the blocking readers:
+/// ```no_run
+/// # async fn run() -> std::io::Result<()> {
+/// # use iso9660_rs::async_reader::AsyncIsoReader;
+/// let image = std::io::Cursor::new(Vec::new()); // tokio::fs::File::open("image.iso").await?
+/// let mut iso = AsyncIsoReader::new(image).await?;
+/// let mut kernel = iso.open("/BOOT/KERNEL").await?;
+/// tokio::io::copy(&mut kernel, &mut tokio::io::sink()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AsyncIsoReader<R: AsyncRead + AsyncSeek + Unpin> {
+    reader: R,
+    size: u64,
+
+    volume_descriptors: VolumeDescriptorList,
+    boot_catalogue: Option<BootCatalogue>,
+    root_directory: DirectoryRef,
+    path_table: PathTableRef,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncIsoReader<R> {
+    pub async fn new(mut reader: R) -> Result<Self, IsoError> {
+        reader.seek(SeekFrom::Start(16 * 2048)).await?;
+        let mut volume_descriptors = VolumeDescriptorList::empty();
+        let mut sector = [0u8; 2048];
+        loop {
+            reader.read_exact(&mut sector).await?;
+            if !volume_descriptors.parse_sector(&sector)? {
+                break;
+            }
+        }
+        let size = reader.seek(SeekFrom::End(0)).await?;
+
+        let boot_catalogue = match volume_descriptors.boot_record() {
+            Some(boot) => {
+                reader
+                    .seek(SeekFrom::Start(boot.catalog_ptr.get() as u64 * 2048))
+                    .await?;
+                reader.read_exact(&mut sector).await?;
+                Some(BootCatalogue::parse(&mut sector.as_slice())?)
+            }
+            None => None,
+        };
+
+        let pvd = volume_descriptors
+            .primary()
+            .ok_or(IsoError::MissingPrimaryDescriptor)?;
+        let root_entry = pvd.dir_record;
+        let root_directory = DirectoryRef {
+            offset: root_entry.header.extent.read() as u64,
+            size: root_entry.header.data_len.read() as u64,
+        };
+
+        let path_table = PathTableRef::new(pvd);
+
+        Ok(Self {
+            reader,
+            size,
+
+            volume_descriptors,
+            boot_catalogue,
+            root_directory,
+            path_table,
+        })
+    }
+
+    /// The size of the underlying image in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn volume_descriptors(&self) -> &VolumeDescriptorList {
+        &self.volume_descriptors
+    }
+
+    /// The El Torito boot catalogue, if the image has a boot record
+    pub fn boot_catalogue(&self) -> Option<&BootCatalogue> {
+        self.boot_catalogue.as_ref()
+    }
+
+    /// Rejects extents that end past the end of the image, since their size can't be trusted
+    fn check_extent(&self, extent: DirectoryRef) -> Result<(), IsoError> {
+        let end = extent
+            .offset
+            .checked_mul(2048)
+            .and_then(|start| start.checked_add(extent.size));
+        match end {
+            Some(end) if end <= self.size => Ok(()),
+            _ => Err(IsoError::ExtentOutOfRange(extent)),
+        }
+    }
+
+    /// Returns the records of the given directory, including the `.` and `..` entries, along
+    /// with their offset from the start of the directory.
+    ///
+    /// The directory is read one sector at a time, since records can't cross a sector boundary.
+    pub async fn read_directory(
+        &mut self,
+        directory: DirectoryRef,
+    ) -> Result<Vec<(u64, DirectoryRecord)>, IsoError> {
+        self.check_extent(directory)?;
+        self.reader
+            .seek(SeekFrom::Start(directory.offset * 2048))
+            .await?;
+        let mut sector = [0u8; 2048];
+        let mut records = Vec::new();
+        let mut start = 0;
+        while start < directory.size {
+            let len = (directory.size - start).min(2048) as usize;
+            self.reader.read_exact(&mut sector[..len]).await?;
+            for entry in SliceDirectoryEntries::new(&sector[..len]) {
+                let (offset, record) = entry?;
+                records.push((start + offset, record.to_record()));
+            }
+            start += len as u64;
+        }
+
+        // The records of a multi-extent file may span several sectors, so they are merged last
+        let mut records = records.into_iter();
+        let mut merged = Vec::new();
+        while let Some((offset, mut record)) = records.next() {
+            record.read_continuation(|| records.next().map(|(_, record)| Ok(record)))?;
+            merged.push((offset, record));
+        }
+        Ok(merged)
+    }
+
+    /// Returns the records of the root directory, see [`AsyncIsoReader::read_directory`]
+    pub async fn root_directory(&mut self) -> Result<Vec<(u64, DirectoryRecord)>, IsoError> {
+        self.read_directory(self.root_directory).await
+    }
+
+    /// Finds the directory at the given path through the little endian path table, see
+    /// [`crate::IsoPathTable::find_directory`].
+    ///
+    /// The table is read one sector at a time, and only until the directory is found.
+    pub async fn find_directory(&mut self, path: &str) -> Result<Option<DirectoryRef>, IsoError> {
+        let table = DirectoryRef {
+            offset: self.path_table.lpath_table_offset,
+            size: self.path_table.size,
+        };
+        self.check_extent(table)?;
+        self.reader
+            .seek(SeekFrom::Start(table.offset * 2048))
+            .await?;
+
+        // Entries are sorted by their parent, so each component of the path comes after the
+        // entry of its parent. Path table indices are 1-based, and the root is the first entry.
+        let mut components = path.split('/').filter(|c| !c.is_empty()).peekable();
+        let mut parent = 0;
+        let mut extent = None;
+        let mut index = 0;
+        // Entries may cross a sector boundary, so the start of an incomplete entry is kept
+        let mut pending = Vec::with_capacity(2 * 2048);
+        let mut sector = [0u8; 2048];
+        let mut read = 0;
+        'table: loop {
+            let mut start = 0;
+            while let Some(&len) = pending.get(start) {
+                if len == 0 {
+                    break 'table;
+                }
+                let size = (size_of::<PathTableEntryHeader>() + len as usize + 1) & !1;
+                if pending.len() - start < size && read < table.size {
+                    break;
+                }
+                let (header, name) = PathTableEntryHeader::split(&pending[start..])?;
+                let entry = PathTableEntryRef { header, name };
+                index += 1;
+                if index == 1 {
+                    parent = 1;
+                    extent = Some(entry.extent());
+                } else if entry.parent_index() as usize == parent
+                    && components.next_if(|c| c.as_bytes() == name).is_some()
+                {
+                    parent = index;
+                    extent = Some(entry.extent());
+                }
+                start = (start + size).min(pending.len());
+                if components.peek().is_none() {
+                    break 'table;
+                }
+            }
+            pending.drain(..start);
+
+            if read >= table.size {
+                break;
+            }
+            let len = (table.size - read).min(2048) as usize;
+            self.reader.read_exact(&mut sector[..len]).await?;
+            pending.extend_from_slice(&sector[..len]);
+            read += len as u64;
+        }
+        let Some(offset) = extent.filter(|_| components.peek().is_none()) else {
+            return Ok(None);
+        };
+
+        // The path table doesn't store the size of the directory, so we read it from the '.'
+        // entry, which is always the first record of the directory
+        let offset = offset as u64;
+        self.reader.seek(SeekFrom::Start(offset * 2048)).await?;
+        self.reader.read_exact(&mut sector).await?;
+        let (header, _) = DirectoryRecordHeader::split(&sector)?;
+        Ok(Some(DirectoryRef {
+            offset,
+            size: header.data_len.read() as u64,
+        }))
+    }
+
+    /// Returns the records of the directory at the given path, see
+    /// [`AsyncIsoReader::read_directory`]
+    pub async fn list(&mut self, path: &str) -> Result<Vec<(u64, DirectoryRecord)>, IsoError> {
+        let directory = self
+            .find_directory(path)
+            .await?
+            .ok_or_else(|| not_found(path))?;
+        self.read_directory(directory).await
+    }
+
+    /// Returns the directory record of the entry at the given path, see
+    /// [`crate::reader::IsoReader::stat`]
+    pub async fn stat(&mut self, path: &str) -> Result<DirectoryRecord, IsoError> {
+        let (parent, name) = split_path(path);
+        if name.is_empty() {
+            return Ok(DirectoryRecord {
+                header: self
+                    .volume_descriptors
+                    .primary()
+                    .ok_or(IsoError::MissingPrimaryDescriptor)?
+                    .dir_record
+                    .header,
+                name: IsoStringFile::from_bytes(&[0x00]),
+                continuation: Vec::new(),
+            });
+        }
+        let directory = self
+            .find_directory(parent)
+            .await?
+            .ok_or_else(|| not_found(path))?;
+        let mut found = None;
+        for (_, record) in self.read_directory(directory).await? {
+            keep_match(&mut found, record, name, false, false);
+        }
+        found.ok_or_else(|| not_found(path))
+    }
+
+    /// Returns the metadata of the entry at the given path, see [`AsyncIsoReader::stat`]
+    pub async fn metadata(&mut self, path: &str) -> Result<Metadata, IsoError> {
+        Ok(self.stat(path).await?.metadata())
+    }
+
+    /// Opens the file at the given path for reading
+    pub async fn open(&mut self, path: &str) -> Result<AsyncIsoFile<&mut R>, IsoError> {
+        let record = self.stat(path).await?;
+        if record.header.is_directory() {
+            return Err(IsoError::IsADirectory(path.to_string()));
+        }
+        Ok(self.open_record(record))
+    }
+
+    /// Opens the file described by the given record
+    pub fn open_record(&mut self, record: DirectoryRecord) -> AsyncIsoFile<&mut R> {
+        AsyncIsoFile::new(&mut self.reader, record)
+    }
+
+    /// Consumes the reader, returning the underlying data
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+fn not_found(path: &str) -> IsoError {
+    IsoError::NotFound(path.to_string())
+}
+
+/// Whether the underlying reader is at the current position of an [`AsyncIsoFile`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SeekState {
+    /// The reader needs to be moved before the next read
+    Unpositioned,
+    /// A seek of the reader was started, but hasn't completed yet
+    Seeking,
+    Positioned,
+}
+
+/// A handle to a file in the image, see [`AsyncIsoReader::open`] and
+/// [`crate::IsoFile`]
+#[derive(Debug)]
+pub struct AsyncIsoFile<T: AsyncRead + AsyncSeek + Unpin> {
+    reader: T,
+    record: DirectoryRecord,
+    /// The extents of the file, in the order they appear in the file
+    extents: Vec<DirectoryRef>,
+    interleave: Option<Interleave>,
+    len: u64,
+    position: u64,
+    state: SeekState,
+}
+
+impl<T: AsyncRead + AsyncSeek + Unpin> AsyncIsoFile<T> {
+    fn new(reader: T, record: DirectoryRecord) -> Self {
+        let extents = record.extents();
+        let interleave = Interleave::from_header(&record.header);
+        Self {
+            reader,
+            record,
+            len: extents.iter().map(|extent| extent.size).sum(),
+            extents,
+            interleave,
+            position: 0,
+            state: SeekState::Unpositioned,
+        }
+    }
+
+    pub fn record(&self) -> &DirectoryRecord {
+        &self.record
+    }
+
+    pub fn metadata(&self) -> Metadata {
+        Metadata::new(&self.record, self.extents.clone())
+    }
+
+    /// The size of the file in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: AsyncRead + AsyncSeek + Unpin> AsyncRead for AsyncIsoFile<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let Some((offset, contiguous)) =
+            directory::locate(&this.extents, this.interleave, this.position)
+        else {
+            return Poll::Ready(Ok(()));
+        };
+        loop {
+            match this.state {
+                SeekState::Unpositioned => {
+                    Pin::new(&mut this.reader).start_seek(SeekFrom::Start(offset))?;
+                    this.state = SeekState::Seeking;
+                }
+                SeekState::Seeking => {
+                    ready!(Pin::new(&mut this.reader).poll_complete(cx))?;
+                    this.state = SeekState::Positioned;
+                }
+                SeekState::Positioned => break,
+            }
+        }
+
+        // Reads are bounded to the current extent or file unit, the next one may not follow it
+        let len = (buf.remaining() as u64).min(contiguous) as usize;
+        let mut bounded = ReadBuf::new(buf.initialize_unfilled_to(len));
+        ready!(Pin::new(&mut this.reader).poll_read(cx, &mut bounded))?;
+        let read = bounded.filled().len();
+        if read == 0 && len > 0 {
+            return Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "image ends before the end of the file",
+            )));
+        }
+        buf.advance(read);
+        this.position += read as u64;
+        if read as u64 == contiguous {
+            this.state = SeekState::Unpositioned;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncRead + AsyncSeek + Unpin> AsyncSeek for AsyncIsoFile<T> {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> std::io::Result<()> {
+        let this = self.get_mut();
+        let position = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => this.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => this.position.checked_add_signed(offset),
+        };
+        this.position = position.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        this.state = SeekState::Unpositioned;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        // The underlying reader is only moved on the next read
+        Poll::Ready(Ok(self.position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::tests::format_image;
+
+    #[tokio::test]
+    async fn test_async_reader() {
+        let kernel: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+        let bytes = format_image(
+            &[
+                ("BOOT/KERNEL", &kernel),
+                ("BOOT/LIMINE.BIN", &[0xAA; 2048]),
+                ("README.TXT", b"hello"),
+            ],
+            Some("BOOT/LIMINE.BIN"),
+        );
+        let mut iso = AsyncIsoReader::new(Cursor::new(bytes)).await.unwrap();
+        assert!(iso.boot_catalogue().unwrap().default_entry().is_valid());
+
+        let root = iso.root_directory().await.unwrap();
+        assert!(
+            root.iter()
+                .any(|(_, record)| record.name.to_str() == "README.TXT")
+        );
+        let boot = iso.list("/BOOT").await.unwrap();
+        // The '.' and '..' entries, along with both files
+        assert_eq!(boot.len(), 4);
+        assert!(iso.metadata("/BOOT").await.unwrap().is_dir());
+
+        let mut file = iso.open("/BOOT/KERNEL").await.unwrap();
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, kernel);
+
+        let mut buf = [0u8; 4];
+        file.seek(SeekFrom::Start(3000)).await.unwrap();
+        file.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, kernel[3000..3004]);
+
+        assert!(matches!(
+            iso.open("/BOOT").await,
+            Err(IsoError::IsADirectory(_))
+        ));
+        assert!(matches!(
+            iso.stat("/BOOT/MISSING").await,
+            Err(IsoError::NotFound(_))
+        ));
+
+        // The image ends in the middle of the kernel
+        let record = iso.stat("/BOOT/KERNEL").await.unwrap();
+        let mut bytes = iso.into_inner().into_inner();
+        bytes.truncate(record.extent().offset as usize * 2048 + 1000);
+        let mut file = AsyncIsoFile::new(Cursor::new(bytes), record);
+        let err = file.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn test_tables_spanning_sectors() {
+        let paths: Vec<String> = (0..100)
+            .map(|i| format!("DIRECTORY_WITH_LONG_NAME_{:03}/F.TXT", i))
+            .collect();
+        let files: Vec<(&str, &[u8])> = paths
+            .iter()
+            .map(|path| (path.as_str(), &b"x"[..]))
+            .collect();
+        let bytes = format_image(&files, None);
+        let mut iso = AsyncIsoReader::new(Cursor::new(bytes)).await.unwrap();
+
+        // Both the root directory and the path table are longer than a sector
+        let root = iso.root_directory().await.unwrap();
+        assert_eq!(root.len(), paths.len() + 2);
+        assert!(root.last().unwrap().0 >= 2048);
+        for path in [&paths[0], &paths[99]] {
+            assert_eq!(iso.metadata(path).await.unwrap().len(), 1);
+        }
+        assert!(
+            iso.find_directory("/DIRECTORY_WITH_LONG_NAME_100")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // The root directory claims to be larger than the image, so nothing is allocated for it
+        let mut bytes = iso.into_inner().into_inner();
+        let root = 16 * 2048 + 156;
+        bytes[root + 10..root + 14].copy_from_slice(&u32::MAX.to_le_bytes());
+        bytes[root + 14..root + 18].copy_from_slice(&u32::MAX.to_be_bytes());
+        let mut iso = AsyncIsoReader::new(Cursor::new(bytes)).await.unwrap();
+        assert!(matches!(
+            iso.root_directory().await,
+            Err(IsoError::ExtentOutOfRange(_))
+        ));
+    }
+}
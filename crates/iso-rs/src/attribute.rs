@@ -0,0 +1,355 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{Read, Seek, SeekFrom};
+
+#[cfg(feature = "std")]
+use crate::directory::DirectoryRecord;
+use crate::{
+    directory::{DirectoryRecordHeader, FileFlags},
+    error::IsoError,
+    types::{DecDateTime, IsoStrA, U16LsbMsb},
+};
+
+/// The fixed part of an extended attribute record, see ECMA-119 9.5.
+///
+/// It is followed by the application use area and the escape sequences, whose lengths are
+/// recorded in the header.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ExtendedAttributeRecordHeader {
+    pub owner: U16LsbMsb,
+    pub group: U16LsbMsb,
+    /// Recorded most significant byte first, see [`Permissions::from_recorded`]
+    pub permissions: [u8; 2],
+    pub creation_date: DecDateTime,
+    pub modification_date: DecDateTime,
+    pub expiration_date: DecDateTime,
+    pub effective_date: DecDateTime,
+    pub record_format: u8,
+    pub record_attributes: u8,
+    pub record_length: U16LsbMsb,
+    pub system_identifier: IsoStrA<32>,
+    pub system_use: [u8; 64],
+    pub version: u8,
+    pub escape_sequences_len: u8,
+    pub reserved: [u8; 64],
+    pub application_use_len: U16LsbMsb,
+}
+
+bitflags::bitflags! {
+    /// The access granted to each class of user, see ECMA-119 9.5.3.
+    ///
+    /// The image records the access that is denied instead, this is converted when reading and
+    /// writing a record.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Permissions: u16 {
+        const SYSTEM_READ = 1 << 0;
+        const SYSTEM_EXECUTE = 1 << 2;
+        const OWNER_READ = 1 << 4;
+        const OWNER_EXECUTE = 1 << 6;
+        const GROUP_READ = 1 << 8;
+        const GROUP_EXECUTE = 1 << 10;
+        const OTHER_READ = 1 << 12;
+        const OTHER_EXECUTE = 1 << 14;
+    }
+}
+
+impl Permissions {
+    /// The odd bits of the field are reserved, and always set
+    const RESERVED: u16 = 0xAAAA;
+
+    /// Converts the permissions field of a record, where set bits deny access
+    pub fn from_recorded(bytes: [u8; 2]) -> Self {
+        Self::from_bits_truncate(!u16::from_be_bytes(bytes))
+    }
+
+    /// The permissions field of a record
+    pub fn to_recorded(self) -> [u8; 2] {
+        (!self.bits() & Self::all().bits() | Self::RESERVED).to_be_bytes()
+    }
+
+    /// The equivalent Unix file mode bits.
+    ///
+    /// Records can't grant write access, so only the owner is given it, which keeps extracted
+    /// files writable and removable. The system class has no Unix equivalent and is ignored.
+    pub fn to_mode(self) -> u32 {
+        let classes = [
+            (Self::OWNER_READ, Self::OWNER_EXECUTE, 6),
+            (Self::GROUP_READ, Self::GROUP_EXECUTE, 3),
+            (Self::OTHER_READ, Self::OTHER_EXECUTE, 0),
+        ];
+        let mut mode = 0o200;
+        for (read, execute, shift) in classes {
+            if self.contains(read) {
+                mode |= 0o4 << shift;
+            }
+            if self.contains(execute) {
+                mode |= 0o1 << shift;
+            }
+        }
+        mode
+    }
+}
+
+/// How the data of a file is organised into records, see ECMA-119 9.5.8
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    NotSpecified,
+    /// Records of [`ExtendedAttributeRecord::record_length`] bytes
+    FixedLength,
+    /// Records preceded by their length, recorded least significant byte first
+    VariableLittleEndian,
+    /// Records preceded by their length, recorded most significant byte first
+    VariableBigEndian,
+    Unknown(u8),
+}
+
+impl RecordFormat {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::NotSpecified,
+            1 => Self::FixedLength,
+            2 => Self::VariableLittleEndian,
+            3 => Self::VariableBigEndian,
+            value => Self::Unknown(value),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::NotSpecified => 0,
+            Self::FixedLength => 1,
+            Self::VariableLittleEndian => 2,
+            Self::VariableBigEndian => 3,
+            Self::Unknown(value) => value,
+        }
+    }
+}
+
+/// The extended attributes of a file, which are recorded in the sectors before its data, see
+/// ECMA-119 9.5
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedAttributeRecord {
+    pub owner: u16,
+    pub group: u16,
+    pub permissions: Permissions,
+    pub creation_date: DecDateTime,
+    pub modification_date: DecDateTime,
+    pub expiration_date: DecDateTime,
+    pub effective_date: DecDateTime,
+    pub record_format: RecordFormat,
+    /// How the records are displayed, 0 for line feed and carriage return characters, 1 for
+    /// FORTRAN vertical spacing characters, 2 if the records contain the control characters
+    pub record_attributes: u8,
+    pub record_length: u16,
+    pub system_identifier: IsoStrA<32>,
+    pub system_use: [u8; 64],
+    pub application_use: Vec<u8>,
+    pub escape_sequences: Vec<u8>,
+}
+
+impl ExtendedAttributeRecord {
+    /// The only version of the record in ECMA-119
+    pub const VERSION: u8 = 1;
+
+    /// A record for a file created now, with the given owner and permissions, and nothing else
+    /// specified
+    pub fn new(owner: u16, group: u16, permissions: Permissions) -> Self {
+        let now = DecDateTime::now();
+        Self {
+            owner,
+            group,
+            permissions,
+            creation_date: now,
+            modification_date: now,
+            expiration_date: DecDateTime::unspecified(),
+            effective_date: DecDateTime::unspecified(),
+            record_format: RecordFormat::NotSpecified,
+            record_attributes: 0,
+            record_length: 0,
+            system_identifier: IsoStrA::empty(),
+            system_use: [0; 64],
+            application_use: Vec::new(),
+            escape_sequences: Vec::new(),
+        }
+    }
+
+    /// Parses a record from the start of the given bytes, which must contain all of it
+    pub fn parse(bytes: &[u8]) -> Result<Self, IsoError> {
+        const HEADER_SIZE: usize = size_of::<ExtendedAttributeRecordHeader>();
+        let invalid = IsoError::InvalidData;
+        if bytes.len() < HEADER_SIZE {
+            return Err(invalid("extended attribute record is truncated"));
+        }
+        let header: &ExtendedAttributeRecordHeader = bytemuck::from_bytes(&bytes[..HEADER_SIZE]);
+        if header.version != Self::VERSION {
+            return Err(invalid("unsupported extended attribute record version"));
+        }
+        let application_end = HEADER_SIZE + header.application_use_len.read() as usize;
+        let escape_end = application_end + header.escape_sequences_len as usize;
+        if escape_end > bytes.len() {
+            return Err(invalid("extended attribute record is truncated"));
+        }
+        Ok(Self {
+            owner: header.owner.read(),
+            group: header.group.read(),
+            permissions: Permissions::from_recorded(header.permissions),
+            creation_date: header.creation_date,
+            modification_date: header.modification_date,
+            expiration_date: header.expiration_date,
+            effective_date: header.effective_date,
+            record_format: RecordFormat::from_u8(header.record_format),
+            record_attributes: header.record_attributes,
+            record_length: header.record_length.read(),
+            system_identifier: header.system_identifier,
+            system_use: header.system_use,
+            application_use: bytes[HEADER_SIZE..application_end].to_vec(),
+            escape_sequences: bytes[application_end..escape_end].to_vec(),
+        })
+    }
+
+    /// The size of the record in bytes, without the padding to the end of its last sector
+    pub fn size(&self) -> usize {
+        size_of::<ExtendedAttributeRecordHeader>()
+            + self.application_use.len()
+            + self.escape_sequences.len()
+    }
+
+    /// The number of sectors taken up by the record, which is what directory records store
+    pub fn sectors(&self) -> u8 {
+        self.size().div_ceil(2048) as u8
+    }
+
+    /// Serializes the record, padded to the end of its last sector.
+    ///
+    /// The application use and escape sequences are limited to 65535 and 255 bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, IsoError> {
+        let application_use_len = u16::try_from(self.application_use.len()).map_err(|_| {
+            IsoError::InvalidInput("extended attribute application use is too long")
+        })?;
+        let escape_sequences_len = u8::try_from(self.escape_sequences.len()).map_err(|_| {
+            IsoError::InvalidInput("extended attribute escape sequences are too long")
+        })?;
+        let header = ExtendedAttributeRecordHeader {
+            owner: U16LsbMsb::new(self.owner),
+            group: U16LsbMsb::new(self.group),
+            permissions: self.permissions.to_recorded(),
+            creation_date: self.creation_date,
+            modification_date: self.modification_date,
+            expiration_date: self.expiration_date,
+            effective_date: self.effective_date,
+            record_format: self.record_format.to_u8(),
+            record_attributes: self.record_attributes,
+            record_length: U16LsbMsb::new(self.record_length),
+            system_identifier: self.system_identifier,
+            system_use: self.system_use,
+            version: Self::VERSION,
+            escape_sequences_len,
+            reserved: [0; 64],
+            application_use_len: U16LsbMsb::new(application_use_len),
+        };
+        let mut bytes = Vec::with_capacity(self.sectors() as usize * 2048);
+        bytes.extend_from_slice(bytemuck::bytes_of(&header));
+        bytes.extend_from_slice(&self.application_use);
+        bytes.extend_from_slice(&self.escape_sequences);
+        bytes.resize(self.sectors() as usize * 2048, 0);
+        Ok(bytes)
+    }
+
+    /// Points the given directory record header, whose data is recorded right after this
+    /// record, at the record, and sets the flags for the fields it specifies.
+    ///
+    /// The owner, group and permissions are flagged together, unless they are all left at their
+    /// defaults of 0 and unrestricted access.
+    pub fn attach(&self, header: &mut DirectoryRecordHeader) {
+        let mut flags = FileFlags::from_bits_retain(header.flags);
+        flags.set(
+            FileFlags::EXTENDED_ATTRIBUTES,
+            self.record_format != RecordFormat::NotSpecified,
+        );
+        flags.set(
+            FileFlags::EXTENDED_PERMISSIONS,
+            self.owner != 0 || self.group != 0 || self.permissions != Permissions::all(),
+        );
+        header.flags = flags.bits();
+        header.extended_attr_record = self.sectors();
+        header
+            .extent
+            .write(header.extent.read() - self.sectors() as u32);
+    }
+
+    /// Reads the record of the given entry, `None` if it doesn't have one
+    #[cfg(feature = "std")]
+    pub fn read<R: Read + Seek>(
+        reader: &mut R,
+        record: &DirectoryRecord,
+    ) -> Result<Option<Self>, IsoError> {
+        let Some(extent) = record.extended_attributes_extent() else {
+            return Ok(None);
+        };
+        let mut bytes = vec![0; extent.size as usize];
+        reader.seek(SeekFrom::Start(extent.offset * 2048))?;
+        reader.read_exact(&mut bytes)?;
+        Self::parse(&bytes).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extended_attribute_record() {
+        assert_eq!(size_of::<ExtendedAttributeRecordHeader>(), 250);
+
+        let mut record = ExtendedAttributeRecord::new(
+            1000,
+            100,
+            Permissions::OWNER_READ | Permissions::OWNER_EXECUTE | Permissions::GROUP_READ,
+        );
+        record.record_format = RecordFormat::FixedLength;
+        record.record_length = 80;
+        record.application_use = b"application".to_vec();
+        record.escape_sequences = b"\x1b%/@".to_vec();
+        let bytes = record.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 2048);
+        // Every class but the owner and group is denied, along with executing for the group
+        assert_eq!(bytes[8..10], [0xFE, 0xAF]);
+        assert_eq!(ExtendedAttributeRecord::parse(&bytes).unwrap(), record);
+        assert_eq!(record.permissions.to_mode(), 0o740);
+        assert_eq!(Permissions::all().to_mode(), 0o755);
+
+        assert!(matches!(
+            ExtendedAttributeRecord::parse(&bytes[..255]),
+            Err(IsoError::InvalidData(_))
+        ));
+        // An owner or group is flagged even when access isn't restricted
+        let permissions_flagged = |record: &ExtendedAttributeRecord| {
+            let mut header: DirectoryRecordHeader = bytemuck::Zeroable::zeroed();
+            header.extent.write(20);
+            record.attach(&mut header);
+            FileFlags::from_bits_retain(header.flags).contains(FileFlags::EXTENDED_PERMISSIONS)
+        };
+        assert!(permissions_flagged(&record));
+        assert!(permissions_flagged(&ExtendedAttributeRecord::new(
+            1000,
+            0,
+            Permissions::all()
+        )));
+        assert!(permissions_flagged(&ExtendedAttributeRecord::new(
+            0,
+            100,
+            Permissions::all()
+        )));
+        assert!(!permissions_flagged(&ExtendedAttributeRecord::new(
+            0,
+            0,
+            Permissions::all()
+        )));
+
+        record.application_use = vec![0; 2048];
+        assert_eq!(record.sectors(), 2);
+        record.escape_sequences = vec![0; 256];
+        assert!(matches!(record.to_bytes(), Err(IsoError::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,430 @@
+use alloc::{string::ToString, vec, vec::Vec};
+
+use crate::{
+    PathTableRef,
+    boot::BootCatalogue,
+    directory::{
+        DirectoryRecord, DirectoryRecordHeader, DirectoryRef, Interleave, Metadata, keep_match,
+        locate,
+    },
+    error::IsoError,
+    slice::{DirectoryRecordRef, SlicePathTable},
+    split_path,
+    types::{Endian, IsoStringFile},
+    volume::VolumeDescriptorList,
+};
+
+/// The size of a logical sector
+pub const SECTOR_SIZE: usize = 2048;
+
+/// A device that can read whole sectors, which is all the `no_std` reader needs.
+///
+/// With `std`, any [`std::io::Read`] and [`std::io::Seek`] can be used through [`IoDevice`].
+pub trait BlockDevice {
+    /// Reads the sector at the given LBA into the buffer
+    fn read_sector(&mut self, lba: u64, buf: &mut [u8; SECTOR_SIZE]) -> Result<(), IsoError>;
+
+    /// The size of the device in bytes, if it is known.
+    ///
+    /// Extents past the end of the device are rejected before anything is allocated for them.
+    /// Without a size, the volume space size recorded in the primary volume descriptor is used.
+    fn size(&mut self) -> Option<u64> {
+        None
+    }
+}
+
+impl<D: BlockDevice + ?Sized> BlockDevice for &mut D {
+    fn read_sector(&mut self, lba: u64, buf: &mut [u8; SECTOR_SIZE]) -> Result<(), IsoError> {
+        (**self).read_sector(lba, buf)
+    }
+
+    fn size(&mut self) -> Option<u64> {
+        (**self).size()
+    }
+}
+
+impl BlockDevice for &[u8] {
+    fn read_sector(&mut self, lba: u64, buf: &mut [u8; SECTOR_SIZE]) -> Result<(), IsoError> {
+        let sector = usize::try_from(lba)
+            .ok()
+            .and_then(|lba| lba.checked_mul(SECTOR_SIZE))
+            .and_then(|start| self.get(start..start.checked_add(SECTOR_SIZE)?))
+            .ok_or(IsoError::Device("sector is past the end of the image"))?;
+        buf.copy_from_slice(sector);
+        Ok(())
+    }
+
+    fn size(&mut self) -> Option<u64> {
+        Some(self.len() as u64)
+    }
+}
+
+/// Adapts a [`std::io::Read`] and [`std::io::Seek`] reader to a [`BlockDevice`]
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoDevice<T: std::io::Read + std::io::Seek>(pub T);
+
+#[cfg(feature = "std")]
+impl<T: std::io::Read + std::io::Seek> BlockDevice for IoDevice<T> {
+    fn read_sector(&mut self, lba: u64, buf: &mut [u8; SECTOR_SIZE]) -> Result<(), IsoError> {
+        self.0
+            .seek(std::io::SeekFrom::Start(lba * SECTOR_SIZE as u64))?;
+        self.0.read_exact(buf)?;
+        Ok(())
+    }
+
+    fn size(&mut self) -> Option<u64> {
+        self.0.seek(std::io::SeekFrom::End(0)).ok()
+    }
+}
+
+/// A read-only ISO image over a [`BlockDevice`], which works without `std`.
+///
+/// This only needs `alloc`, for buffering directories and the path table, so it can be used from
+/// bootloaders and kernels:
+/// ```no_run
+/// # use iso9660_rs::block::BlockIsoReader;
+/// static IMAGE: &[u8] = &[]; // A driver implementing `BlockDevice`
+/// let mut iso = BlockIsoReader::new(IMAGE).unwrap();
+/// let kernel = iso.read_file("/BOOT/KERNEL").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct BlockIsoReader<D: BlockDevice> {
+    device: D,
+    /// The size of the device in bytes, which bounds every extent that is read
+    size: u64,
+
+    volume_descriptors: VolumeDescriptorList,
+    boot_catalogue: Option<BootCatalogue>,
+    root_directory: DirectoryRef,
+    path_table: PathTableRef,
+}
+
+impl<D: BlockDevice> BlockIsoReader<D> {
+    pub fn new(mut device: D) -> Result<Self, IsoError> {
+        let mut volume_descriptors = VolumeDescriptorList::empty();
+        let mut sector = [0u8; SECTOR_SIZE];
+        let mut lba = 16;
+        loop {
+            device.read_sector(lba, &mut sector)?;
+            if !volume_descriptors.parse_sector(&sector)? {
+                break;
+            }
+            lba += 1;
+        }
+
+        let boot_catalogue = match volume_descriptors.boot_record() {
+            Some(boot) => {
+                device.read_sector(boot.catalog_ptr.get() as u64, &mut sector)?;
+                Some(BootCatalogue::from_bytes(&sector)?)
+            }
+            None => None,
+        };
+
+        let pvd = volume_descriptors
+            .primary()
+            .ok_or(IsoError::MissingPrimaryDescriptor)?;
+        let root_entry = pvd.dir_record;
+        let root_directory = DirectoryRef {
+            offset: root_entry.header.extent.read() as u64,
+            size: root_entry.header.data_len.read() as u64,
+        };
+
+        let path_table = PathTableRef::new(pvd);
+        let size = match device.size() {
+            Some(size) => size,
+            None => pvd.volume_space_size.read() as u64 * SECTOR_SIZE as u64,
+        };
+
+        Ok(Self {
+            device,
+            size,
+
+            volume_descriptors,
+            boot_catalogue,
+            root_directory,
+            path_table,
+        })
+    }
+
+    pub fn volume_descriptors(&self) -> &VolumeDescriptorList {
+        &self.volume_descriptors
+    }
+
+    /// The El Torito boot catalogue, if the image has a boot record
+    pub fn boot_catalogue(&self) -> Option<&BootCatalogue> {
+        self.boot_catalogue.as_ref()
+    }
+
+    /// Reads bytes of the given extent, starting at the given offset into it, returning the
+    /// number of bytes read, which is 0 at the end of the extent
+    pub fn read_extent_at(
+        &mut self,
+        extent: DirectoryRef,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, IsoError> {
+        let len = (buf.len() as u64).min(extent.size.saturating_sub(offset)) as usize;
+        let mut sector = [0u8; SECTOR_SIZE];
+        let mut read = 0;
+        while read < len {
+            let position = offset + read as u64;
+            let start = (position % SECTOR_SIZE as u64) as usize;
+            let count = (len - read).min(SECTOR_SIZE - start);
+            self.device
+                .read_sector(extent.offset + position / SECTOR_SIZE as u64, &mut sector)?;
+            buf[read..read + count].copy_from_slice(&sector[start..start + count]);
+            read += count;
+        }
+        Ok(len)
+    }
+
+    /// Reads a whole extent into memory, after checking that it lies within the device
+    fn read_extent(&mut self, extent: DirectoryRef) -> Result<Vec<u8>, IsoError> {
+        self.check_extent(extent)?;
+        let mut data = vec![0; extent.size as usize];
+        self.read_extent_at(extent, 0, &mut data)?;
+        Ok(data)
+    }
+
+    /// Rejects extents that end past the end of the device, since their size can't be trusted
+    fn check_extent(&self, extent: DirectoryRef) -> Result<(), IsoError> {
+        let end = extent
+            .offset
+            .checked_mul(SECTOR_SIZE as u64)
+            .and_then(|start| start.checked_add(extent.size));
+        match end {
+            Some(end) if end <= self.size => Ok(()),
+            _ => Err(IsoError::ExtentOutOfRange(extent)),
+        }
+    }
+
+    /// Iterates over the records of the given directory one sector at a time, see
+    /// [`BlockDirectoryEntries`]
+    fn directory_entries(
+        &mut self,
+        directory: DirectoryRef,
+    ) -> Result<BlockDirectoryEntries<'_, D>, IsoError> {
+        self.check_extent(directory)?;
+        Ok(BlockDirectoryEntries {
+            device: &mut self.device,
+            directory,
+            sector: [0; SECTOR_SIZE],
+            loaded: None,
+            offset: 0,
+        })
+    }
+
+    /// Returns the records of the given directory, including the `.` and `..` entries, along
+    /// with their offset from the start of the directory
+    pub fn read_directory(
+        &mut self,
+        directory: DirectoryRef,
+    ) -> Result<Vec<(u64, DirectoryRecord)>, IsoError> {
+        self.directory_entries(directory)?.collect()
+    }
+
+    /// Returns the records of the root directory, see [`BlockIsoReader::read_directory`]
+    pub fn root_directory(&mut self) -> Result<Vec<(u64, DirectoryRecord)>, IsoError> {
+        self.read_directory(self.root_directory)
+    }
+
+    /// Finds the directory at the given path through the little endian path table, see
+    /// [`crate::slice::IsoSlice::find_directory`]
+    pub fn find_directory(&mut self, path: &str) -> Result<Option<DirectoryRef>, IsoError> {
+        let table = self.read_extent(DirectoryRef {
+            offset: self.path_table.lpath_table_offset,
+            size: self.path_table.size,
+        })?;
+        let Some(entry) = SlicePathTable::new(&table).find_path(path)? else {
+            return Ok(None);
+        };
+
+        // The path table doesn't store the size of the directory, so we read it from the '.'
+        // entry, which is always the first record of the directory
+        let offset = entry.extent() as u64;
+        let mut sector = [0u8; SECTOR_SIZE];
+        self.device.read_sector(offset, &mut sector)?;
+        let (header, _) = DirectoryRecordHeader::split(&sector)?;
+        Ok(Some(DirectoryRef {
+            offset,
+            size: header.data_len.read() as u64,
+        }))
+    }
+
+    /// Returns the directory record of the entry at the given path
+    pub fn stat(&mut self, path: &str) -> Result<DirectoryRecord, IsoError> {
+        let not_found = || IsoError::NotFound(path.to_string());
+        let (parent, name) = split_path(path);
+        if name.is_empty() {
+            return Ok(DirectoryRecord {
+                header: self
+                    .volume_descriptors
+                    .primary()
+                    .ok_or(IsoError::MissingPrimaryDescriptor)?
+                    .dir_record
+                    .header,
+                name: IsoStringFile::from_bytes(&[0x00]),
+                continuation: Vec::new(),
+            });
+        }
+        let directory = self.find_directory(parent)?.ok_or_else(not_found)?;
+        let mut found = None;
+        for entry in self.directory_entries(directory)? {
+            let (_, record) = entry?;
+            keep_match(&mut found, record, name, false, false);
+        }
+        found.ok_or_else(not_found)
+    }
+
+    /// Returns the metadata of the entry at the given path, see [`BlockIsoReader::stat`]
+    pub fn metadata(&mut self, path: &str) -> Result<Metadata, IsoError> {
+        Ok(self.stat(path)?.metadata())
+    }
+
+    /// Reads the whole file at the given path into memory
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, IsoError> {
+        let record = self.stat(path)?;
+        if record.header.is_directory() {
+            return Err(IsoError::IsADirectory(path.to_string()));
+        }
+        let extents = record.extents();
+        let interleave = Interleave::from_header(&record.header);
+        let mut data = Vec::new();
+        // Every contiguous run of the file starts at a sector, either an extent or a file unit
+        while let Some((offset, len)) = locate(&extents, interleave, data.len() as u64) {
+            data.extend_from_slice(&self.read_extent(DirectoryRef {
+                offset: offset / 2048,
+                size: len,
+            })?);
+        }
+        Ok(data)
+    }
+
+    /// Consumes the reader, returning the underlying device
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+}
+
+/// The records of a directory on a [`BlockDevice`], along with their offset from the start of
+/// the directory.
+///
+/// Records can't cross a sector boundary, so only the sector being parsed is kept in memory, and
+/// the records of each multi-extent file are merged into its first record.
+struct BlockDirectoryEntries<'d, D: BlockDevice> {
+    device: &'d mut D,
+    directory: DirectoryRef,
+    sector: [u8; SECTOR_SIZE],
+    /// The index of the sector in the buffer, relative to the start of the directory
+    loaded: Option<u64>,
+    offset: u64,
+}
+
+impl<D: BlockDevice> BlockDirectoryEntries<'_, D> {
+    /// Reads the next record, without merging multi-extent files
+    fn next_record(&mut self) -> Option<Result<(u64, DirectoryRecord), IsoError>> {
+        while self.offset < self.directory.size {
+            let index = self.offset / SECTOR_SIZE as u64;
+            if self.loaded != Some(index) {
+                let lba = self.directory.offset + index;
+                if let Err(err) = self.device.read_sector(lba, &mut self.sector) {
+                    self.offset = self.directory.size;
+                    return Some(Err(err));
+                }
+                self.loaded = Some(index);
+            }
+
+            let start = (self.offset % SECTOR_SIZE as u64) as usize;
+            let end = (self.directory.size - index * SECTOR_SIZE as u64).min(SECTOR_SIZE as u64);
+            let data = &self.sector[start..end as usize];
+            if data[0] == 0 {
+                // The rest of the sector is padding, so the next record is in the next sector
+                self.offset = (index + 1) * SECTOR_SIZE as u64;
+                continue;
+            }
+
+            let offset = self.offset;
+            return match DirectoryRecordHeader::split(data) {
+                Ok((header, name)) => {
+                    self.offset += header.len as u64;
+                    Some(Ok((
+                        offset,
+                        DirectoryRecordRef { header, name }.to_record(),
+                    )))
+                }
+                Err(err) => {
+                    // We can't know where the next record starts, so we stop here
+                    self.offset = self.directory.size;
+                    Some(Err(err))
+                }
+            };
+        }
+        None
+    }
+}
+
+impl<D: BlockDevice> Iterator for BlockDirectoryEntries<'_, D> {
+    type Item = Result<(u64, DirectoryRecord), IsoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.next_record()?;
+        Some(entry.and_then(|(offset, mut record)| {
+            record.read_continuation(|| self.next_record().map(|next| next.map(|(_, r)| r)))?;
+            Ok((offset, record))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::format_image;
+
+    #[test]
+    fn test_block_reader() {
+        let kernel: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+        let bytes = format_image(
+            &[("BOOT/KERNEL", &kernel), ("BOOT/LIMINE.BIN", &[0xAA; 2048])],
+            Some("BOOT/LIMINE.BIN"),
+        );
+        let mut iso = BlockIsoReader::new(bytes.as_slice()).unwrap();
+        assert!(iso.boot_catalogue().unwrap().default_entry().is_valid());
+        assert_eq!(iso.read_file("/BOOT/KERNEL").unwrap(), kernel);
+        assert_eq!(iso.root_directory().unwrap().len(), 4);
+
+        let extent = iso.metadata("/BOOT/KERNEL").unwrap().extents()[0];
+        let mut buf = [0u8; 100];
+        assert_eq!(iso.read_extent_at(extent, 4950, &mut buf).unwrap(), 50);
+        assert_eq!(buf[..50], kernel[4950..]);
+
+        assert!(matches!(
+            iso.read_file("/BOOT"),
+            Err(IsoError::IsADirectory(_))
+        ));
+        assert!(matches!(
+            iso.stat("/MISSING/KERNEL"),
+            Err(IsoError::NotFound(_))
+        ));
+
+        let mut iso = BlockIsoReader::new(IoDevice(std::io::Cursor::new(bytes.clone()))).unwrap();
+        assert_eq!(iso.read_file("/BOOT/KERNEL").unwrap(), kernel);
+
+        // The sizes of the root directory and the path table are past the end of the image, so
+        // nothing is allocated for them
+        let mut corrupt = bytes;
+        let pvd = 16 * SECTOR_SIZE;
+        corrupt[pvd + 132..pvd + 136].copy_from_slice(&u32::MAX.to_le_bytes());
+        corrupt[pvd + 156 + 10..pvd + 156 + 14].copy_from_slice(&u32::MAX.to_le_bytes());
+        corrupt[pvd + 156 + 14..pvd + 156 + 18].copy_from_slice(&u32::MAX.to_be_bytes());
+        let mut iso = BlockIsoReader::new(corrupt.as_slice()).unwrap();
+        assert!(matches!(
+            iso.root_directory(),
+            Err(IsoError::ExtentOutOfRange(_))
+        ));
+        assert!(matches!(
+            iso.stat("/BOOT/KERNEL"),
+            Err(IsoError::ExtentOutOfRange(_))
+        ));
+    }
+}
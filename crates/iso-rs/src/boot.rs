@@ -1,10 +1,13 @@
-use std::{
-    fmt::Debug,
-    io::{Read, Write},
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Debug;
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+use crate::{
+    error::IsoError,
+    types::{Endian, LittleEndian, U16, U32},
 };
 
-use crate::types::{Endian, IsoStrA, LittleEndian, U16, U32};
-
 /// Types for El Torito boot catalogue
 /// The boot catalogue consists of a series of boot catalogue entries:
 /// First, the validation entry
@@ -31,23 +34,58 @@ impl BootCatalogue {
 
     /// Parse the boot catalogue from the given reader,
     /// expects the reader to seek to the start of the catalogue
-    pub fn parse<T: Read>(reader: &mut T) -> Result<Self, std::io::Error> {
-        let validation = BootValidationEntry::parse(reader)?;
-        if !validation.is_valid() {
-            panic!("Invalid boot catalogue: Validation entry is invalid");
+    #[cfg(feature = "std")]
+    pub fn parse<T: Read>(reader: &mut T) -> Result<Self, IsoError> {
+        Self::parse_entries(true, || {
+            let mut buffer = [0u8; 32];
+            reader.read_exact(&mut buffer)?;
+            Ok(buffer)
+        })
+    }
+
+    /// Like [`BootCatalogue::parse`], but doesn't verify the validation entry, so catalogues
+    /// with a bad checksum can still be read
+    #[cfg(feature = "std")]
+    pub fn parse_unchecked<T: Read>(reader: &mut T) -> Result<Self, IsoError> {
+        Self::parse_entries(false, || {
+            let mut buffer = [0u8; 32];
+            reader.read_exact(&mut buffer)?;
+            Ok(buffer)
+        })
+    }
+
+    /// Parse the boot catalogue from the start of the given bytes, usually the sector it was
+    /// read from
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, IsoError> {
+        let mut entries = bytes.chunks_exact(32);
+        Self::parse_entries(true, || {
+            entries
+                .next()
+                .map(|entry| entry.try_into().unwrap())
+                .ok_or(IsoError::InvalidBootCatalogue("catalogue is truncated"))
+        })
+    }
+
+    /// Parses the catalogue from a source of 32 byte entries
+    fn parse_entries(
+        validate: bool,
+        mut next_entry: impl FnMut() -> Result<[u8; 32], IsoError>,
+    ) -> Result<Self, IsoError> {
+        let validation: BootValidationEntry = bytemuck::cast(next_entry()?);
+        if validate {
+            validation.validate()?;
         }
-        let default_entry = BootSectionEntry::parse(reader)?;
+        let default_entry: BootSectionEntry = bytemuck::cast(next_entry()?);
         if !default_entry.is_valid() {
-            panic!("Invalid boot catalogue: Default boot entry is invalid");
+            return Err(IsoError::InvalidBootCatalogue("default entry is invalid"));
         }
 
         let mut sections = Vec::new();
-        let mut buffer = [0u8; 32];
         let mut has_more = false;
         let mut header = None;
         let mut entries = Vec::new();
         loop {
-            reader.read_exact(&mut buffer)?;
+            let buffer = next_entry()?;
             match buffer[0] {
                 0x00 if !has_more => break,
                 0x90 => {
@@ -66,16 +104,22 @@ impl BootCatalogue {
                     }
                     header = Some(bytemuck::cast(buffer));
                 }
-                id => {
+                _ => {
                     if header.is_none() {
-                        panic!("Boot catalogue: expected header, got: {:#x}", id);
+                        return Err(IsoError::InvalidBootCatalogue(
+                            "section entry without a section header",
+                        ));
                     }
                     entries.push(bytemuck::cast(buffer));
                 }
             }
         }
 
-        assert!(!has_more, "Boot catalogue: expected more sections");
+        if has_more {
+            return Err(IsoError::InvalidBootCatalogue(
+                "expected more section headers",
+            ));
+        }
         if let Some(header) = header {
             sections.push((header, entries));
         }
@@ -87,7 +131,20 @@ impl BootCatalogue {
         })
     }
 
-    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), std::io::Error> {
+    pub fn validation(&self) -> &BootValidationEntry {
+        &self.validation
+    }
+
+    pub fn default_entry(&self) -> &BootSectionEntry {
+        &self.default_entry
+    }
+
+    pub fn sections(&self) -> &[(BootSectionHeaderEntry, Vec<BootSectionEntry>)] {
+        &self.sections
+    }
+
+    #[cfg(feature = "std")]
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), IsoError> {
         writer.write_all(bytemuck::bytes_of(&self.validation))?;
         writer.write_all(bytemuck::bytes_of(&self.default_entry))?;
         for (header, entries) in self.sections.iter() {
@@ -198,15 +255,18 @@ impl BootValidationEntry {
     }
 }
 
+impl Default for BootValidationEntry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Debug for BootValidationEntry {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("BootValidationEntry")
             .field("header_id", &format!("{:#x}", self.header_id))
             .field("platform_id", &PlatformId::from_u8(self.platform_id))
-            .field(
-                "manufacturer",
-                &core::str::from_utf8(&self.manufacturer).unwrap(),
-            )
+            .field("manufacturer", &String::from_utf8_lossy(&self.manufacturer))
             .field("checksum", &self.checksum.get())
             .field("key", &self.key)
             .finish_non_exhaustive()
@@ -214,14 +274,37 @@ impl Debug for BootValidationEntry {
 }
 
 impl BootValidationEntry {
-    pub fn parse<T: Read>(reader: &mut T) -> Result<Self, std::io::Error> {
+    #[cfg(feature = "std")]
+    pub fn parse<T: Read>(reader: &mut T) -> Result<Self, IsoError> {
         let mut buf: [u8; 32] = [0; 32];
         reader.read_exact(&mut buf)?;
         Ok(bytemuck::cast(buf))
     }
 
     pub fn is_valid(&self) -> bool {
-        self.header_id == 0x01 && self.checksum.get() == self.calculate_checksum()
+        self.validate().is_ok()
+    }
+
+    /// Checks the header ID, key and checksum of the entry
+    pub fn validate(&self) -> Result<(), IsoError> {
+        if self.header_id != 0x01 {
+            return Err(IsoError::InvalidBootCatalogue(
+                "validation entry has an invalid header ID",
+            ));
+        }
+        if self.key != [0x55, 0xAA] {
+            return Err(IsoError::InvalidBootCatalogue(
+                "validation entry has an invalid key",
+            ));
+        }
+        let expected = self.calculate_checksum();
+        if self.checksum.get() != expected {
+            return Err(IsoError::BadChecksum {
+                expected,
+                found: self.checksum.get(),
+            });
+        }
+        Ok(())
     }
 
     pub fn calculate_checksum(&self) -> u16 {
@@ -249,14 +332,14 @@ pub struct BootSectionHeaderEntry {
 }
 
 impl Debug for BootSectionHeaderEntry {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("BootSectionHeaderEntry")
             .field("header_type", &format!("{:#x}", self.header_type))
             .field("platform_id", &PlatformId::from_u8(self.platform_id))
             .field("section_count", &self.section_count.get())
             .field(
                 "section_ident",
-                &core::str::from_utf8(&self.section_ident).unwrap(),
+                &String::from_utf8_lossy(&self.section_ident),
             )
             .finish_non_exhaustive()
     }
@@ -320,7 +403,7 @@ impl BootSectionEntry {
 }
 
 impl Debug for BootSectionEntry {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("BootSectionHeaderEntry")
             .field("boot_indicator", &format!("{:#x}", self.boot_indicator))
             .field("boot_media_type", &MediaType::from_u8(self.boot_media_type))
@@ -334,7 +417,8 @@ impl Debug for BootSectionEntry {
 }
 
 impl BootSectionEntry {
-    pub fn parse<T: Read>(reader: &mut T) -> Result<Self, std::io::Error> {
+    #[cfg(feature = "std")]
+    pub fn parse<T: Read>(reader: &mut T) -> Result<Self, IsoError> {
         let mut buf: [u8; 32] = [0; 32];
         reader.read_exact(&mut buf)?;
         Ok(bytemuck::cast(buf))
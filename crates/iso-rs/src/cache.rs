@@ -0,0 +1,358 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+/// Hit and miss counts of a [`SectorCache`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Reads that were served from the cache
+    pub hits: u64,
+    /// Reads that had to load a sector from the underlying device
+    pub misses: u64,
+    /// Sectors that were dropped to make room for others
+    pub evictions: u64,
+}
+
+/// Marks the end of the recency list
+const NONE: usize = usize::MAX;
+
+#[derive(Debug)]
+struct CachedSector {
+    sector: u64,
+    data: Box<[u8]>,
+    /// The slot of the next more recently used sector
+    newer: usize,
+    /// The slot of the next less recently used sector
+    older: usize,
+}
+
+/// A least recently used cache of sectors in front of a device.
+///
+/// This can be placed between a reader and [`crate::reader::IsoReader`] or
+/// [`crate::IsoImage`], so that path lookups and repeated listings of the same directories
+/// don't go back to the device each time:
+/// ```no_run
+/// # use iso9660_rs::{cache::SectorCache, reader::IsoReader};
+/// let file = std::fs::File::open("image.iso").unwrap();
+/// let mut iso = IsoReader::new(SectorCache::new(file, 256)).unwrap();
+/// ```
+///
+/// Reads of whole sectors, such as streaming a file through [`crate::IsoFile`], bypass the cache
+/// so that they don't evict the directory sectors used by lookups. Writes go straight through to
+/// the device, and update any cached copies of the sectors they touch.
+#[derive(Debug)]
+pub struct SectorCache<T> {
+    inner: T,
+    /// The maximum number of sectors to keep
+    capacity: usize,
+    position: u64,
+
+    /// The cached sectors, linked from the most to the least recently used
+    slots: Vec<CachedSector>,
+    /// The slot of each cached sector
+    index: HashMap<u64, usize>,
+    newest: usize,
+    oldest: usize,
+    stats: CacheStats,
+}
+
+impl<T> SectorCache<T> {
+    /// Creates a cache which holds up to `capacity` sectors
+    pub fn new(inner: T, capacity: usize) -> Self {
+        Self {
+            inner,
+            capacity,
+            position: 0,
+
+            slots: Vec::new(),
+            index: HashMap::new(),
+            newest: NONE,
+            oldest: NONE,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of sectors currently cached
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// Drops every cached sector, this must be called if the device was modified through
+    /// [`SectorCache::get_mut`]
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.index.clear();
+        self.newest = NONE;
+        self.oldest = NONE;
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the underlying device, writes through it are not seen by the cache, see
+    /// [`SectorCache::clear`]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> SectorCache<T> {
+    /// Removes the slot from the recency list
+    fn unlink(&mut self, slot: usize) {
+        let CachedSector { newer, older, .. } = self.slots[slot];
+        match newer {
+            NONE => self.newest = older,
+            newer => self.slots[newer].older = older,
+        }
+        match older {
+            NONE => self.oldest = newer,
+            older => self.slots[older].newer = newer,
+        }
+    }
+
+    /// Inserts the slot at the front of the recency list
+    fn push_newest(&mut self, slot: usize) {
+        self.slots[slot].newer = NONE;
+        self.slots[slot].older = self.newest;
+        match self.newest {
+            NONE => self.oldest = slot,
+            newest => self.slots[newest].newer = slot,
+        }
+        self.newest = slot;
+    }
+}
+
+impl<T: Read + Seek> SectorCache<T> {
+    /// Returns the cached sector, loading it from the device if needed.
+    ///
+    /// The last sector of the device may be shorter than a full sector.
+    fn sector(&mut self, sector: u64) -> Result<&[u8], std::io::Error> {
+        let slot = match self.index.get(&sector) {
+            Some(&slot) => {
+                self.stats.hits += 1;
+                self.unlink(slot);
+                slot
+            }
+            None => {
+                self.stats.misses += 1;
+                let mut data = Vec::with_capacity(2048);
+                self.inner.seek(SeekFrom::Start(sector * 2048))?;
+                (&mut self.inner).take(2048).read_to_end(&mut data)?;
+                let cached = CachedSector {
+                    sector,
+                    data: data.into_boxed_slice(),
+                    newer: NONE,
+                    older: NONE,
+                };
+
+                // Even without any capacity, the sector is kept until the next one is loaded
+                let slot = if self.index.len() >= self.capacity.max(1) {
+                    let oldest = self.oldest;
+                    self.unlink(oldest);
+                    self.index.remove(&self.slots[oldest].sector);
+                    self.stats.evictions += 1;
+                    self.slots[oldest] = cached;
+                    oldest
+                } else {
+                    self.slots.push(cached);
+                    self.slots.len() - 1
+                };
+                self.index.insert(sector, slot);
+                slot
+            }
+        };
+
+        self.push_newest(slot);
+        Ok(&self.slots[slot].data)
+    }
+}
+
+impl<T: Read + Seek> Read for SectorCache<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let offset = (self.position % 2048) as usize;
+        if offset == 0 && buf.len() >= 2048 {
+            // Whole sectors are read straight from the device, which is always up to date
+            let len = buf.len() / 2048 * 2048;
+            self.inner.seek(SeekFrom::Start(self.position))?;
+            let read = self.inner.read(&mut buf[..len])?;
+            self.position += read as u64;
+            return Ok(read);
+        }
+
+        let sector = self.sector(self.position / 2048)?;
+        let len = buf.len().min(sector.len().saturating_sub(offset));
+        buf[..len].copy_from_slice(&sector[offset..offset + len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl<T: Seek> Seek for SectorCache<T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(offset) => offset,
+            // Only the device knows where it ends
+            SeekFrom::End(_) => self.inner.seek(pos)?,
+            SeekFrom::Current(offset) => {
+                self.position.checked_add_signed(offset).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "invalid seek to a negative or overflowing position",
+                    )
+                })?
+            }
+        };
+        Ok(self.position)
+    }
+}
+
+impl<T: Write + Seek> Write for SectorCache<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.seek(SeekFrom::Start(self.position))?;
+        let written = self.inner.write(buf)?;
+
+        // Keep the cached copies of the sectors that were written in sync
+        let start = self.position;
+        let end = start + written as u64;
+        for sector in start / 2048..end.div_ceil(2048) {
+            let Some(&slot) = self.index.get(&sector) else {
+                continue;
+            };
+            let cached = &mut self.slots[slot];
+            let sector_start = sector * 2048;
+            let from = start.max(sector_start);
+            let to = end.min(sector_start + 2048);
+            let len = (to - sector_start) as usize;
+            if cached.data.len() < len {
+                // The write extended the last sector of the device
+                let mut data = cached.data.to_vec();
+                data.resize(len, 0);
+                cached.data = data.into_boxed_slice();
+            }
+            cached.data[(from - sector_start) as usize..len]
+                .copy_from_slice(&buf[(from - start) as usize..(to - start) as usize]);
+        }
+
+        self.position = end;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{reader::IsoReader, tests::format_image};
+
+    #[test]
+    fn test_cached_lookups() {
+        let big: Vec<u8> = (0..64 * 1024u32).map(|i| (i % 251) as u8).collect();
+        let bytes = format_image(
+            &[
+                ("BOOT/GRUB/GRUB.CFG", b"grub"),
+                ("README.TXT", b"readme"),
+                ("BIG.BIN", &big),
+            ],
+            None,
+        );
+        let mut iso = IsoReader::new(SectorCache::new(Cursor::new(bytes), 16)).unwrap();
+        let lookup = |iso: &mut IsoReader<_>| {
+            iso.stat("/BOOT/GRUB/GRUB.CFG").unwrap();
+            iso.open_directory("/BOOT").unwrap().entries().unwrap();
+        };
+        lookup(&mut iso);
+        let cold = iso.get_ref().stats();
+        assert!(cold.misses > 0);
+
+        for _ in 0..10 {
+            lookup(&mut iso);
+        }
+        let warm = iso.get_ref().stats();
+        assert_eq!(warm.misses, cold.misses);
+        assert!(warm.hits > cold.hits);
+
+        let mut data = String::new();
+        iso.open("/README.TXT")
+            .unwrap()
+            .read_to_string(&mut data)
+            .unwrap();
+        assert_eq!(data, "readme");
+
+        // Streaming a file larger than the cache doesn't evict the directories
+        let mut data = Vec::new();
+        iso.open("/BIG.BIN")
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, big);
+        let before = iso.get_ref().stats();
+        lookup(&mut iso);
+        let after = iso.get_ref().stats();
+        assert_eq!(after.misses, before.misses);
+        assert_eq!(after.evictions, 0);
+    }
+
+    #[test]
+    fn test_eviction_and_writes() {
+        let bytes: Vec<u8> = (0..4 * 2048 + 100).map(|i| (i / 2048) as u8).collect();
+        let mut cache = SectorCache::new(Cursor::new(bytes), 2);
+        let mut byte = [0u8; 1];
+        for sector in [0, 1, 0, 2] {
+            cache.seek(SeekFrom::Start(sector * 2048)).unwrap();
+            cache.read_exact(&mut byte).unwrap();
+            assert_eq!(byte[0], sector as u8);
+        }
+        // Sector 1 was the least recently used when sector 2 was loaded
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 3,
+                evictions: 1
+            }
+        );
+
+        // Writes that straddle a cached sector are visible through the cache
+        cache.seek(SeekFrom::Start(2048 - 2)).unwrap();
+        cache.write_all(&[0xFF; 4]).unwrap();
+        let mut buf = [0u8; 4];
+        cache.seek(SeekFrom::Start(2048 - 2)).unwrap();
+        cache.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [0xFF; 4]);
+
+        // The last sector is short, and reads stop at its end
+        assert_eq!(cache.seek(SeekFrom::End(-10)).unwrap(), 4 * 2048 + 90);
+        let mut rest = Vec::new();
+        cache.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, [4; 10]);
+        assert_eq!(cache.get_ref().get_ref()[2048], 0xFF);
+    }
+}
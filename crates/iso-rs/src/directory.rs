@@ -1,6 +1,18 @@
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
 use std::io::Write;
 
-use crate::types::{IsoStringFile, U16LsbMsb, U32LsbMsb};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Datelike, FixedOffset, Offset, TimeZone, Timelike};
+
+use crate::{
+    error::IsoError,
+    types::{FileIdentifier, IsoStringFile, U16LsbMsb, U32LsbMsb},
+};
+
+/// The largest extent a single directory record describes, in whole sectors so that the extents
+/// of a multi-extent file are contiguous
+pub const MAX_EXTENT_SIZE: u64 = u32::MAX as u64 & !2047;
 
 /// The header of a directory record, because the identifier is variable length,
 #[repr(C)]
@@ -49,15 +61,62 @@ impl DirectoryRecordHeader {
     pub fn is_directory(&self) -> bool {
         FileFlags::from_bits_retain(self.flags).contains(FileFlags::DIRECTORY)
     }
+
+    /// Whether the record describes the associated file of the entry with the same identifier,
+    /// such as a resource fork, see [`FileFlags::ASSOCIATED_FILE`]
+    pub fn is_associated(&self) -> bool {
+        FileFlags::from_bits_retain(self.flags).contains(FileFlags::ASSOCIATED_FILE)
+    }
+
+    /// Splits the directory record at the start of the given bytes into its header and its file
+    /// identifier, without copying either.
+    ///
+    /// The length of the record is validated against the size of the header and the file
+    /// identifier, and against the number of bytes available.
+    pub fn split(bytes: &[u8]) -> Result<(&Self, &[u8]), IsoError> {
+        const HEADER_SIZE: usize = size_of::<DirectoryRecordHeader>();
+        let invalid = IsoError::InvalidData;
+        if bytes.len() < HEADER_SIZE {
+            return Err(invalid("directory record header is truncated"));
+        }
+        let header = Self::from_bytes(&bytes[..HEADER_SIZE]);
+        let len = header.len as usize;
+        let name_end = HEADER_SIZE + header.file_identifier_len as usize;
+        if len < name_end {
+            return Err(invalid(
+                "directory record is shorter than its file identifier",
+            ));
+        }
+        if len > bytes.len() {
+            return Err(invalid(
+                "directory record extends past the end of the sector",
+            ));
+        }
+        Ok((header, &bytes[HEADER_SIZE..name_end]))
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct DirectoryRecord {
     pub header: DirectoryRecordHeader,
     pub name: IsoStringFile,
+    /// The extents of the records that follow this one, if it is the first record of a
+    /// multi-extent file, see [`FileFlags::NOT_FINAL`]
+    pub continuation: Vec<DirectoryRef>,
 }
 
 impl DirectoryRecord {
+    /// Parses a directory record from the start of the given bytes, see
+    /// [`DirectoryRecordHeader::split`]
+    pub fn parse(bytes: &[u8]) -> Result<Self, IsoError> {
+        let (header, name) = DirectoryRecordHeader::split(bytes)?;
+        Ok(Self {
+            header: *header,
+            name: IsoStringFile::from_bytes(name),
+            continuation: Vec::new(),
+        })
+    }
+
     pub fn size(&self) -> usize {
         size_of::<DirectoryRecordHeader>() + self.name.len()
     }
@@ -76,7 +135,7 @@ impl DirectoryRecord {
                 extended_attr_record: 0,
                 extent: U32LsbMsb::new(dir_ref.offset as u32),
                 data_len: U32LsbMsb::new(dir_ref.size as u32),
-                date_time: DirDateTime::default(),
+                date_time: DirDateTime::now(),
                 flags: flags.bits(),
                 file_unit_size: 0,
                 interleave_gap_size: 0,
@@ -84,14 +143,131 @@ impl DirectoryRecord {
                 file_identifier_len: name.len() as u8,
             },
             name: IsoStringFile::from_bytes(name),
+            continuation: Vec::new(),
+        }
+    }
+
+    /// Returns the records describing a file of any size.
+    ///
+    /// Files larger than `max_extent_size` are split into contiguous extents, and every record
+    /// but the last has [`FileFlags::NOT_FINAL`] set. The size is usually [`MAX_EXTENT_SIZE`],
+    /// and must be a multiple of the sector size no larger than it.
+    pub fn new_multi_extent(
+        name: &[u8],
+        file: DirectoryRef,
+        flags: FileFlags,
+        max_extent_size: u64,
+    ) -> Result<Vec<Self>, IsoError> {
+        if max_extent_size == 0
+            || !max_extent_size.is_multiple_of(2048)
+            || max_extent_size > MAX_EXTENT_SIZE
+        {
+            return Err(IsoError::InvalidInput(
+                "extents must be whole sectors no larger than the maximum extent size",
+            ));
         }
+        let mut records = Vec::new();
+        let mut extent = file;
+        while extent.size > max_extent_size {
+            let first = DirectoryRef {
+                offset: extent.offset,
+                size: max_extent_size,
+            };
+            records.push(Self::new(name, first, flags | FileFlags::NOT_FINAL));
+            extent.offset += max_extent_size / 2048;
+            extent.size -= max_extent_size;
+        }
+        records.push(Self::new(name, extent, flags));
+        Ok(records)
+    }
+
+    /// The extent of the data described by the header of this record, which follows its
+    /// extended attribute record
+    pub fn extent(&self) -> DirectoryRef {
+        DirectoryRef {
+            offset: self.header.extent.read() as u64 + self.header.extended_attr_record as u64,
+            size: self.header.data_len.read() as u64,
+        }
+    }
+
+    /// The sectors of the extended attribute record of the entry, `None` if it doesn't have
+    /// one, see [`crate::attribute::ExtendedAttributeRecord`]
+    pub fn extended_attributes_extent(&self) -> Option<DirectoryRef> {
+        match self.header.extended_attr_record {
+            0 => None,
+            sectors => Some(DirectoryRef {
+                offset: self.header.extent.read() as u64,
+                size: sectors as u64 * 2048,
+            }),
+        }
+    }
+
+    /// Every extent of the entry, including those of the records that continue a multi-extent
+    /// file
+    pub fn extents(&self) -> Vec<DirectoryRef> {
+        let mut extents = vec![self.extent()];
+        extents.extend_from_slice(&self.continuation);
+        extents
+    }
+
+    /// Adds the extents of the records that continue a multi-extent file to this record.
+    ///
+    /// `next` returns the records that follow this one in the directory. Nothing is read unless
+    /// this record has [`FileFlags::NOT_FINAL`] set.
+    pub(crate) fn read_continuation(
+        &mut self,
+        mut next: impl FnMut() -> Option<Result<DirectoryRecord, IsoError>>,
+    ) -> Result<(), IsoError> {
+        let mut flags = FileFlags::from_bits_retain(self.header.flags);
+        while flags.contains(FileFlags::NOT_FINAL) {
+            let record = next().ok_or(IsoError::InvalidData(
+                "multi-extent file is missing its final record",
+            ))??;
+            if record.name.bytes() != self.name.bytes() {
+                return Err(IsoError::InvalidData(
+                    "records of a multi-extent file have different identifiers",
+                ));
+            }
+            flags = FileFlags::from_bits_retain(record.header.flags);
+            self.continuation.push(record.extent());
+        }
+        Ok(())
+    }
+
+    /// The file identifier of the record, or `None` for the `.` and `..` records and
+    /// identifiers that aren't valid UTF-8
+    pub fn identifier(&self) -> Option<FileIdentifier<'_>> {
+        FileIdentifier::from_record(self.name.bytes(), self.header.is_directory())
+    }
+
+    /// The version number of a file, e.g. 1 for `README.TXT;1`
+    pub fn version(&self) -> Option<u16> {
+        self.identifier().and_then(|identifier| identifier.version)
+    }
+
+    /// Whether this is the record of the entry with the given name, see
+    /// [`FileIdentifier::matches`]
+    pub fn matches(&self, name: &str, case_insensitive: bool) -> bool {
+        let query = if self.header.is_directory() {
+            FileIdentifier::directory(name)
+        } else {
+            FileIdentifier::parse(name)
+        };
+        self.identifier()
+            .is_some_and(|identifier| identifier.matches(&query, case_insensitive))
+    }
+
+    /// Returns the metadata of the entry described by this record
+    pub fn metadata(&self) -> Metadata {
+        Metadata::new(self, self.extents())
     }
 
-    pub fn write<W: Write>(&self, writer: &mut W) -> Result<usize, std::io::Error> {
+    #[cfg(feature = "std")]
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<usize, IsoError> {
         let mut written = 0;
-        writer.write_all(&self.header.to_bytes())?;
+        writer.write_all(self.header.to_bytes())?;
         written += size_of::<DirectoryRecordHeader>();
-        writer.write_all(&self.name.bytes())?;
+        writer.write_all(self.name.bytes())?;
         written += self.name.len();
         if written < self.header.len as usize {
             for _ in 0..(self.header.len as usize - written) {
@@ -102,6 +278,91 @@ impl DirectoryRecord {
     }
 }
 
+/// The decoded metadata of a file or directory, similar to [`std::fs::Metadata`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    len: u64,
+    date_time: DirDateTime,
+    flags: FileFlags,
+    version: Option<u16>,
+    extents: Vec<DirectoryRef>,
+    interleave: Option<Interleave>,
+}
+
+impl Metadata {
+    pub(crate) fn new(record: &DirectoryRecord, extents: Vec<DirectoryRef>) -> Self {
+        Self {
+            len: extents.iter().map(|extent| extent.size).sum(),
+            date_time: record.header.date_time,
+            flags: FileFlags::from_bits_retain(record.header.flags),
+            // Directories don't have a version, so a ';' in their name is part of the name
+            version: record.version(),
+            extents,
+            interleave: Interleave::from_header(&record.header),
+        }
+    }
+
+    /// The size of the file in bytes, or the size of the directory records of a directory
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The recording date of the entry, as it is stored in the image
+    pub fn date_time(&self) -> DirDateTime {
+        self.date_time
+    }
+
+    /// The recording date of the entry, `None` if it was not recorded
+    #[cfg(feature = "chrono")]
+    pub fn modified(&self) -> Option<DateTime<FixedOffset>> {
+        self.date_time.to_datetime()
+    }
+
+    pub fn flags(&self) -> FileFlags {
+        self.flags
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.flags.contains(FileFlags::DIRECTORY)
+    }
+
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.flags.contains(FileFlags::HIDDEN)
+    }
+
+    pub fn is_associated(&self) -> bool {
+        self.flags.contains(FileFlags::ASSOCIATED_FILE)
+    }
+
+    /// Whether the file is stored in more than one extent
+    pub fn is_multi_extent(&self) -> bool {
+        self.flags.contains(FileFlags::NOT_FINAL) || self.extents.len() > 1
+    }
+
+    /// The version number of the file, e.g. 1 for `README.TXT;1`
+    pub fn version(&self) -> Option<u16> {
+        self.version
+    }
+
+    /// The extents of the file, in the order they appear in the file
+    pub fn extents(&self) -> &[DirectoryRef] {
+        &self.extents
+    }
+
+    /// The layout of the file if it is interleaved
+    pub fn interleave(&self) -> Option<Interleave> {
+        self.interleave
+    }
+}
+
 /// The root directory entry
 #[repr(C)]
 #[derive(Default, Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
@@ -112,7 +373,7 @@ pub struct RootDirectoryEntry {
 }
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct DirDateTime {
     /// Number of years since 1900
     year: u8,
@@ -121,30 +382,62 @@ pub struct DirDateTime {
     hour: u8,
     minute: u8,
     second: u8,
+    /// Offset from GMT in 15 minute intervals, as a signed number
     offset: u8,
 }
 
-impl Default for DirDateTime {
-    fn default() -> Self {
+impl DirDateTime {
+    /// The current time, or an unrecorded date if the clock isn't available, which is the case
+    /// without the `std` and `chrono` features
+    pub fn now() -> Self {
+        #[cfg(all(feature = "std", feature = "chrono"))]
+        return Self::from_datetime(&chrono::Utc::now());
+        #[cfg(not(all(feature = "std", feature = "chrono")))]
+        return Self::default();
+    }
+
+    /// Converts a date time into a recording date, years before 1900 or after 2155 can't be
+    /// represented, and are clamped.
+    #[cfg(feature = "chrono")]
+    pub fn from_datetime<Tz: TimeZone>(date_time: &DateTime<Tz>) -> Self {
+        let offset = date_time.offset().fix().local_minus_utc() / (15 * 60);
         Self {
-            year: 0,
-            month: 0,
-            day: 0,
-            hour: 0,
-            minute: 0,
-            second: 0,
-            offset: 0,
+            year: (date_time.year() - 1900).clamp(0, 255) as u8,
+            month: date_time.month() as u8,
+            day: date_time.day() as u8,
+            hour: date_time.hour() as u8,
+            minute: date_time.minute() as u8,
+            second: date_time.second() as u8,
+            offset: offset as i8 as u8,
         }
     }
+
+    /// Converts the recording date into a date time, returns `None` if the date was not recorded
+    /// or is invalid.
+    #[cfg(feature = "chrono")]
+    pub fn to_datetime(&self) -> Option<DateTime<FixedOffset>> {
+        let offset = FixedOffset::east_opt(self.offset as i8 as i32 * 15 * 60)?;
+        offset
+            .with_ymd_and_hms(
+                1900 + self.year as i32,
+                self.month as u32,
+                self.day as u32,
+                self.hour as u32,
+                self.minute as u32,
+                self.second as u32,
+            )
+            .single()
+    }
 }
 
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct DirectoryRef {
     pub offset: u64,
     pub size: u64,
 }
 
 bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct FileFlags: u8 {
         const HIDDEN = 0b0000_0001;
         const DIRECTORY = 0b0000_0010;
@@ -154,3 +447,245 @@ bitflags::bitflags! {
         const NOT_FINAL = 0b1000_0000;
     }
 }
+
+/// The layout of an interleaved file, see ECMA-119 6.4.3.
+///
+/// The data of an interleaved file is recorded in file units of `file_unit_size` sectors, with
+/// `gap_size` sectors between them that belong to other data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interleave {
+    pub file_unit_size: u8,
+    pub gap_size: u8,
+}
+
+impl Interleave {
+    pub fn new(file_unit_size: u8, gap_size: u8) -> Result<Self, IsoError> {
+        if file_unit_size == 0 {
+            return Err(IsoError::InvalidInput("file units can't be empty"));
+        }
+        Ok(Self {
+            file_unit_size,
+            gap_size,
+        })
+    }
+
+    /// The interleaving of the file described by the given header, or `None` if it isn't
+    /// interleaved
+    pub fn from_header(header: &DirectoryRecordHeader) -> Option<Self> {
+        Self::new(header.file_unit_size, header.interleave_gap_size).ok()
+    }
+
+    fn unit_len(&self) -> u64 {
+        self.file_unit_size as u64 * 2048
+    }
+
+    /// Maps an offset into the data of the file to an offset into its extent, along with the
+    /// number of bytes left in the file unit
+    pub fn map(&self, offset: u64) -> (u64, u64) {
+        let stride = (self.file_unit_size as u64 + self.gap_size as u64) * 2048;
+        let within = offset % self.unit_len();
+        (
+            offset / self.unit_len() * stride + within,
+            self.unit_len() - within,
+        )
+    }
+
+    /// The number of sectors taken up by an extent with the given length of data, including
+    /// the gaps between its file units
+    pub fn sectors(&self, len: u64) -> u64 {
+        match len.div_ceil(self.unit_len()) {
+            0 => 0,
+            units => {
+                let last = len - (units - 1) * self.unit_len();
+                (units - 1) * (self.file_unit_size as u64 + self.gap_size as u64)
+                    + last.div_ceil(2048)
+            }
+        }
+    }
+}
+
+/// Returns the byte offset in the image of the given position in a file, along with the number
+/// of bytes of the file that are stored contiguously from there, or `None` at the end of the
+/// file
+pub(crate) fn locate(
+    extents: &[DirectoryRef],
+    interleave: Option<Interleave>,
+    mut position: u64,
+) -> Option<(u64, u64)> {
+    for extent in extents {
+        if position < extent.size {
+            let left = extent.size - position;
+            let (offset, contiguous) = match interleave {
+                Some(interleave) => interleave.map(position),
+                None => (position, left),
+            };
+            return Some((extent.offset * 2048 + offset, contiguous.min(left)));
+        }
+        position -= extent.size;
+    }
+    None
+}
+
+/// Keeps the record with the given name, preferring the highest version of a file, see
+/// [`DirectoryRecord::matches`].
+///
+/// Only associated files are kept if `associated` is set, and only other records otherwise.
+pub(crate) fn keep_match(
+    found: &mut Option<DirectoryRecord>,
+    record: DirectoryRecord,
+    name: &str,
+    case_insensitive: bool,
+    associated: bool,
+) {
+    if record.header.is_associated() == associated
+        && record.matches(name, case_insensitive)
+        && found
+            .as_ref()
+            .is_none_or(|found| record.version() > found.version())
+    {
+        *found = Some(record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_multi_extent() {
+        let file = DirectoryRef {
+            offset: 100,
+            size: 2 * 8192 + 100,
+        };
+        let records =
+            DirectoryRecord::new_multi_extent(b"BIG.BIN", file, FileFlags::empty(), 8192).unwrap();
+        let extents: Vec<(DirectoryRef, bool)> = records
+            .iter()
+            .map(|record| {
+                (
+                    record.extent(),
+                    FileFlags::from_bits_retain(record.header.flags).contains(FileFlags::NOT_FINAL),
+                )
+            })
+            .collect();
+        assert_eq!(
+            extents,
+            [
+                (
+                    DirectoryRef {
+                        offset: 100,
+                        size: 8192
+                    },
+                    true
+                ),
+                (
+                    DirectoryRef {
+                        offset: 104,
+                        size: 8192
+                    },
+                    true
+                ),
+                (
+                    DirectoryRef {
+                        offset: 108,
+                        size: 100
+                    },
+                    false
+                ),
+            ]
+        );
+        let single = DirectoryRecord::new_multi_extent(
+            b"BIG.BIN",
+            file,
+            FileFlags::empty(),
+            MAX_EXTENT_SIZE,
+        )
+        .unwrap();
+        assert_eq!(single.len(), 1);
+
+        // Extents are whole sectors, and can't be larger than a directory record can describe
+        for max_extent_size in [0, 1000, MAX_EXTENT_SIZE + 2048] {
+            assert!(matches!(
+                DirectoryRecord::new_multi_extent(
+                    b"BIG.BIN",
+                    file,
+                    FileFlags::empty(),
+                    max_extent_size
+                ),
+                Err(IsoError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_multi_extent_file() {
+        use std::io::{Cursor, Read, Seek, SeekFrom};
+
+        use crate::{
+            reader::IsoReader,
+            tests::{format_options, options, stage_files},
+        };
+
+        const EXTENT_SIZE: u64 = 8192;
+        let data: Vec<u8> = (0..EXTENT_SIZE * 2 + 100).map(|i| i as u8).collect();
+        let (_root, mut files) = stage_files(&[("BIG.BIN", &data), ("SMALL.TXT", b"small")]);
+        // The associated file has the same identifier, and is recorded first
+        files
+            .set_associated("BIG.BIN", crate::file::FileData::Data(b"fork".to_vec()))
+            .unwrap();
+        let bytes = format_options(options(files), EXTENT_SIZE).unwrap();
+        let mut iso = IsoReader::new(Cursor::new(bytes.clone())).unwrap();
+
+        // The file is stored as three records, but listed as one entry after its associated file
+        let names: Vec<(String, bool)> = iso
+            .walk()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.path.starts_with("BIG"))
+            .map(|entry| (entry.path, entry.record.header.is_associated()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("BIG.BIN".to_string(), true),
+                ("BIG.BIN".to_string(), false)
+            ]
+        );
+
+        let metadata = iso.metadata("/BIG.BIN").unwrap();
+        assert!(metadata.is_multi_extent());
+        assert_eq!(metadata.len(), data.len() as u64);
+        assert_eq!(metadata.extents().len(), 3);
+        assert_eq!(metadata.extents()[0].size, EXTENT_SIZE);
+
+        let mut file = iso.open("/BIG.BIN").unwrap();
+        assert_eq!(file.len(), data.len() as u64);
+        file.seek(SeekFrom::Start(EXTENT_SIZE - 2)).unwrap();
+        let mut straddling = [0; 4];
+        file.read_exact(&mut straddling).unwrap();
+        assert_eq!(straddling, data[EXTENT_SIZE as usize - 2..][..4]);
+        file.rewind().unwrap();
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+        assert_eq!(iso.root_directory().read_file("BIG.BIN").unwrap(), data);
+        assert!(!iso.metadata("/SMALL.TXT").unwrap().is_multi_extent());
+
+        let slice = crate::slice::IsoSlice::new(&bytes).unwrap();
+        assert_eq!(slice.read("/BIG.BIN").unwrap(), data);
+        let mut block = crate::block::BlockIsoReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(block.read_file("/BIG.BIN").unwrap(), data);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_dir_date_time() {
+        assert!(DirDateTime::default().to_datetime().is_none());
+
+        let offset = FixedOffset::west_opt(5 * 3600 + 30 * 60).unwrap();
+        let date_time = offset.with_ymd_and_hms(1998, 7, 14, 23, 59, 1).unwrap();
+        let recorded = DirDateTime::from_datetime(&date_time);
+        assert_eq!(recorded.offset as i8, -22);
+        assert_eq!(recorded.to_datetime(), Some(date_time));
+    }
+}
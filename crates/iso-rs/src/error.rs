@@ -0,0 +1,116 @@
+use alloc::string::String;
+use core::fmt::Display;
+
+use crate::{directory::DirectoryRef, path::PathTableKind};
+
+/// An error from parsing, reading or creating an image.
+///
+/// Every public API returns this instead of panicking, so malformed or hostile images can be
+/// handled by the caller. With `std`, it converts to and from [`std::io::Error`], so it can be
+/// used with `?` in either direction.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum IsoError {
+    /// An error from the underlying reader or writer
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    /// The block device failed to read a sector, see [`crate::block::BlockDevice`]
+    Device(&'static str),
+    /// A structure in the image is malformed
+    InvalidData(&'static str),
+    /// A volume descriptor doesn't have the `CD001` identifier, which means either the volume
+    /// descriptor set isn't at LBA 16, or it isn't terminated
+    InvalidDescriptor,
+    /// The volume descriptor set doesn't contain a primary volume descriptor
+    MissingPrimaryDescriptor,
+    /// The El Torito boot catalogue is malformed
+    InvalidBootCatalogue(&'static str),
+    /// The checksum of the boot catalogue validation entry doesn't match its contents
+    BadChecksum { expected: u16, found: u16 },
+    /// A copy of the path table disagrees with the others, starting at the given 1-based entry,
+    /// see [`crate::IsoPathTable::verify`]
+    CorruptPathTable { table: PathTableKind, entry: usize },
+    /// An extent lies past the end of the image
+    ExtentOutOfRange(DirectoryRef),
+    /// A file or directory name can't be represented, e.g. because it isn't valid UTF-8
+    InvalidName(String),
+    /// The boot image given in [`crate::ElToritoOptions`] is not one of the files in the image
+    MissingBootImage(String),
+    /// The files or options given to create an image are invalid
+    InvalidInput(&'static str),
+    /// There is no entry at the given path
+    NotFound(String),
+    /// The entry at the given path is a directory, but a file was expected
+    IsADirectory(String),
+}
+
+impl Display for IsoError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            IsoError::Io(err) => write!(f, "{}", err),
+            IsoError::Device(msg) => write!(f, "block device error: {}", msg),
+            IsoError::InvalidData(msg) => write!(f, "{}", msg),
+            IsoError::InvalidDescriptor => write!(f, "invalid volume descriptor"),
+            IsoError::MissingPrimaryDescriptor => write!(f, "primary volume descriptor not found"),
+            IsoError::InvalidBootCatalogue(msg) => write!(f, "invalid boot catalogue: {}", msg),
+            IsoError::BadChecksum { expected, found } => write!(
+                f,
+                "boot catalogue checksum is {:#06x}, expected {:#06x}",
+                found, expected
+            ),
+            IsoError::CorruptPathTable { table, entry } => {
+                write!(f, "{} path table is corrupt at entry {}", table, entry)
+            }
+            IsoError::ExtentOutOfRange(extent) => write!(
+                f,
+                "extent of {} bytes at LBA {} is past the end of the image",
+                extent.size, extent.offset
+            ),
+            IsoError::InvalidName(name) => write!(f, "invalid name: {}", name),
+            IsoError::MissingBootImage(path) => write!(f, "boot image {} not found", path),
+            IsoError::InvalidInput(msg) => write!(f, "{}", msg),
+            IsoError::NotFound(path) => write!(f, "{} not found", path),
+            IsoError::IsADirectory(path) => write!(f, "{} is a directory", path),
+        }
+    }
+}
+
+impl core::error::Error for IsoError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            IsoError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for IsoError {
+    fn from(err: std::io::Error) -> Self {
+        IsoError::Io(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<IsoError> for std::io::Error {
+    fn from(err: IsoError) -> Self {
+        use std::io::ErrorKind;
+        let kind = match err {
+            IsoError::Io(err) => return err,
+            IsoError::Device(_) => ErrorKind::Other,
+            IsoError::InvalidData(_)
+            | IsoError::InvalidDescriptor
+            | IsoError::MissingPrimaryDescriptor
+            | IsoError::InvalidBootCatalogue(_)
+            | IsoError::BadChecksum { .. }
+            | IsoError::CorruptPathTable { .. }
+            | IsoError::ExtentOutOfRange(_) => ErrorKind::InvalidData,
+            IsoError::InvalidName(_) | IsoError::InvalidInput(_) => ErrorKind::InvalidInput,
+            IsoError::NotFound(_) | IsoError::MissingBootImage(_) => ErrorKind::NotFound,
+            IsoError::IsADirectory(_) => ErrorKind::IsADirectory,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
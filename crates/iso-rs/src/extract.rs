@@ -0,0 +1,358 @@
+#[cfg(feature = "chrono")]
+use std::time::SystemTime;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    attribute::Permissions,
+    directory::{DirectoryRecord, FileFlags},
+    error::IsoError,
+    reader::IsoReader,
+    split_path,
+};
+
+/// An entry that was not extracted, see [`ExtractReport`]
+#[derive(Debug, Clone)]
+pub struct SkippedEntry {
+    /// The path of the entry in the image
+    pub path: String,
+    pub reason: &'static str,
+}
+
+/// An entry that was extracted under a different name, see [`ExtractReport`]
+#[derive(Debug, Clone)]
+pub struct RenamedEntry {
+    /// The path of the entry in the image
+    pub path: String,
+    /// The path the entry was written to on the host
+    pub host_path: PathBuf,
+}
+
+/// A summary of what [`IsoReader::extract`] wrote to the host
+#[derive(Debug, Clone, Default)]
+pub struct ExtractReport {
+    pub files: usize,
+    pub directories: usize,
+    pub skipped: Vec<SkippedEntry>,
+    pub renamed: Vec<RenamedEntry>,
+}
+
+/// Tracks the host paths written by an extraction, so entries that map to the same host path
+/// are renamed instead of overwriting each other.
+struct Extraction {
+    report: ExtractReport,
+    used: HashSet<PathBuf>,
+    /// Directories that were created, in the order they were created, with their recorded
+    /// permissions
+    directories: Vec<(PathBuf, DirectoryRecord, Option<Permissions>)>,
+}
+
+/// Converts a file identifier into a name that can be created on the host.
+///
+/// The version suffix (`;1`) and the trailing dot of names without an extension are not part of
+/// the name, so they are removed. Returns `None` if the name can't be used on the host, and
+/// whether the name had to be changed otherwise.
+fn host_name(record: &DirectoryRecord) -> Option<(String, bool)> {
+    let name = String::from_utf8_lossy(record.name.bytes());
+    let changed = name.as_bytes() != record.name.bytes();
+    let name = match name.rsplit_once(';') {
+        Some((name, _version)) if !record.header.is_directory() => name,
+        _ => &name,
+    };
+    let name = name.strip_suffix('.').unwrap_or(name);
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        return None;
+    }
+    Some((name.to_string(), changed))
+}
+
+/// Applies the recording date of the entry to the host file or directory
+#[cfg(feature = "chrono")]
+fn set_modified(path: &Path, record: &DirectoryRecord) -> Result<(), IsoError> {
+    if let Some(date_time) = record.header.date_time.to_datetime() {
+        let file = if record.header.is_directory() {
+            fs::File::open(path)?
+        } else {
+            fs::File::options().write(true).open(path)?
+        };
+        file.set_modified(SystemTime::from(date_time))?;
+    }
+    Ok(())
+}
+
+/// Recording dates can't be converted without the `chrono` feature, so the host keeps the time
+/// of the extraction
+#[cfg(not(feature = "chrono"))]
+fn set_modified(_path: &Path, _record: &DirectoryRecord) -> Result<(), IsoError> {
+    Ok(())
+}
+
+/// Applies the recorded permissions of the entry to the host file or directory, see
+/// [`Permissions::to_mode`]
+#[cfg(unix)]
+fn set_permissions(path: &Path, permissions: Option<Permissions>) -> Result<(), IsoError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(permissions) = permissions {
+        fs::set_permissions(path, fs::Permissions::from_mode(permissions.to_mode()))?;
+    }
+    Ok(())
+}
+
+/// Only Unix hosts have an equivalent of the recorded permissions, so the host files keep the
+/// default permissions of the process elsewhere
+#[cfg(not(unix))]
+fn set_permissions(_path: &Path, _permissions: Option<Permissions>) -> Result<(), IsoError> {
+    Ok(())
+}
+
+impl<R: Read + Seek> IsoReader<R> {
+    /// Extracts the entry at the given path in the image into the destination directory,
+    /// which is created if it doesn't exist.
+    ///
+    /// If the path is a directory, its contents are extracted recursively. File contents are
+    /// streamed, and with the `chrono` feature the recorded timestamps are applied to everything
+    /// that is written. Entries that can't be represented on the host are skipped or renamed,
+    /// which is recorded in the returned report.
+    ///
+    /// On Unix, the permissions of extended attribute records are applied, see
+    /// [`Permissions::to_mode`]. The owner and group are not, and entries without recorded
+    /// permissions are created with the default permissions of the process.
+    pub fn extract(&mut self, path: &str, destination: &Path) -> Result<ExtractReport, IsoError> {
+        fs::create_dir_all(destination)?;
+        let mut extraction = Extraction {
+            report: ExtractReport::default(),
+            used: HashSet::new(),
+            directories: Vec::new(),
+        };
+
+        let record = self.stat(path)?;
+        if !record.header.is_directory() {
+            let (_, name) = split_path(path);
+            self.extract_entry(name.to_string(), record, destination, &mut extraction)?;
+            return Ok(extraction.report);
+        }
+
+        // The walk borrows the reader, so we collect the tree before writing anything
+        let entries = self
+            .open_directory(path)?
+            .walk()
+            .collect::<Result<Vec<_>, _>>()?;
+        // Maps directories in the image to where they were created on the host
+        let mut parents = HashMap::from([(String::new(), destination.to_path_buf())]);
+        for entry in entries {
+            let (parent, _) = entry.path.rsplit_once('/').unwrap_or(("", ""));
+            let Some(parent) = parents.get(parent).cloned() else {
+                extraction.report.skipped.push(SkippedEntry {
+                    path: entry.path,
+                    reason: "parent directory was skipped",
+                });
+                continue;
+            };
+            let is_directory = entry.is_directory();
+            let host_path =
+                self.extract_entry(entry.path.clone(), entry.record, &parent, &mut extraction)?;
+            if let (true, Some(host_path)) = (is_directory, host_path) {
+                parents.insert(entry.path, host_path);
+            }
+        }
+
+        // Creating entries updates the modification time of their parent, so directories are
+        // timestamped last, children before their parents. Their permissions may not allow
+        // creating entries either, so they are applied then too.
+        for (host_path, record, permissions) in extraction.directories.iter().rev() {
+            set_modified(host_path, record)?;
+            set_permissions(host_path, *permissions)?;
+        }
+
+        Ok(extraction.report)
+    }
+
+    /// The permissions recorded for the entry, `None` if it has no extended attribute record or
+    /// the record doesn't specify them
+    fn recorded_permissions(
+        &mut self,
+        record: &DirectoryRecord,
+    ) -> Result<Option<Permissions>, IsoError> {
+        let flags = FileFlags::from_bits_retain(record.header.flags);
+        if !flags.contains(FileFlags::EXTENDED_PERMISSIONS) {
+            return Ok(None);
+        }
+        Ok(self
+            .extended_attributes_of(record)?
+            .map(|attributes| attributes.permissions))
+    }
+
+    /// Extracts a single entry into the given host directory, returning the path it was
+    /// written to, or `None` if it was skipped
+    fn extract_entry(
+        &mut self,
+        path: String,
+        record: DirectoryRecord,
+        parent: &Path,
+        extraction: &mut Extraction,
+    ) -> Result<Option<PathBuf>, IsoError> {
+        if record.header.is_associated() {
+            extraction.report.skipped.push(SkippedEntry {
+                path,
+                reason: "associated files can't be represented on the host",
+            });
+            return Ok(None);
+        }
+        let Some((name, mut renamed)) = host_name(&record) else {
+            extraction.report.skipped.push(SkippedEntry {
+                path,
+                reason: "name can't be represented on the host",
+            });
+            return Ok(None);
+        };
+
+        let mut host_path = parent.join(&name);
+        let mut suffix = 1;
+        while extraction.used.contains(&host_path) {
+            host_path = parent.join(format!("{}~{}", name, suffix));
+            suffix += 1;
+            renamed = true;
+        }
+        extraction.used.insert(host_path.clone());
+        if renamed {
+            extraction.report.renamed.push(RenamedEntry {
+                path,
+                host_path: host_path.clone(),
+            });
+        }
+
+        let permissions = self.recorded_permissions(&record)?;
+        if record.header.is_directory() {
+            fs::create_dir_all(&host_path)?;
+            extraction.report.directories += 1;
+            extraction
+                .directories
+                .push((host_path.clone(), record, permissions));
+        } else {
+            let mut file = self.open_record(record.clone());
+            std::io::copy(&mut file, &mut fs::File::create(&host_path)?)?;
+            set_modified(&host_path, &record)?;
+            set_permissions(&host_path, permissions)?;
+            extraction.report.files += 1;
+        }
+        Ok(Some(host_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::tests::{format_image, format_input, stage_files};
+
+    #[test]
+    fn test_extract() {
+        let bytes = format_image(
+            &[
+                ("BOOT/GRUB/GRUB.CFG", b"grub"),
+                ("EFI/BOOT/BOOTX64.EFI", b"MZ"),
+                ("README.TXT", b"readme"),
+            ],
+            None,
+        );
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+        let output = tempfile::tempdir().unwrap();
+
+        let report = iso.extract("/", output.path()).unwrap();
+        assert_eq!(report.files, 3);
+        assert_eq!(report.directories, 4);
+        assert!(report.skipped.is_empty());
+        assert!(report.renamed.is_empty());
+        assert_eq!(
+            fs::read(output.path().join("EFI/BOOT/BOOTX64.EFI")).unwrap(),
+            b"MZ"
+        );
+
+        #[cfg(feature = "chrono")]
+        {
+            let recorded = iso.stat("/BOOT/GRUB").unwrap().header.date_time;
+            let modified = fs::metadata(output.path().join("BOOT/GRUB"))
+                .unwrap()
+                .modified()
+                .unwrap();
+            assert_eq!(modified, SystemTime::from(recorded.to_datetime().unwrap()));
+        }
+
+        let subtree = tempfile::tempdir().unwrap();
+        let report = iso.extract("/BOOT", subtree.path()).unwrap();
+        assert_eq!(report.files, 1);
+        assert_eq!(
+            fs::read(subtree.path().join("GRUB/GRUB.CFG")).unwrap(),
+            b"grub"
+        );
+    }
+
+    #[test]
+    fn test_extract_renamed() {
+        let bytes = format_image(&[("A.TXT", b"a"), ("B.TXT", b"b")], None);
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+        let (offset, _) = iso
+            .root_directory()
+            .entries()
+            .unwrap()
+            .into_iter()
+            .find(|(_, entry)| entry.name.to_str() == "B.TXT")
+            .unwrap();
+        let root = iso.stat("/").unwrap().header.extent.read() as u64;
+
+        // Rename B.TXT to A.TXT, so both entries map to the same host path
+        let mut bytes = iso.into_inner().into_inner();
+        let name = (root * 2048 + offset) as usize + 33;
+        bytes[name..name + 5].copy_from_slice(b"A.TXT");
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        let report = iso.extract("/", output.path()).unwrap();
+        assert_eq!(report.files, 2);
+        assert_eq!(report.renamed.len(), 1);
+        assert_eq!(report.renamed[0].host_path, output.path().join("A.TXT~1"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::attribute::ExtendedAttributeRecord;
+
+        let (_root, mut files) = stage_files(&[
+            ("BIN/TOOL", b"tool"),
+            ("PRIVATE.TXT", b"private"),
+            ("PLAIN.TXT", b"plain"),
+        ]);
+        let tool = Permissions::OWNER_READ | Permissions::OWNER_EXECUTE | Permissions::OTHER_READ;
+        files
+            .set_extended_attributes("BIN/TOOL", ExtendedAttributeRecord::new(0, 0, tool))
+            .unwrap();
+        let private = ExtendedAttributeRecord::new(0, 0, Permissions::OWNER_READ);
+        files
+            .set_extended_attributes("PRIVATE.TXT", private)
+            .unwrap();
+        let bytes = format_input(files, None, Vec::new()).unwrap();
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        iso.extract("/", output.path()).unwrap();
+        let mode = |path: &str| {
+            let metadata = fs::metadata(output.path().join(path)).unwrap();
+            metadata.permissions().mode() & 0o777
+        };
+        assert_eq!(mode("BIN/TOOL"), 0o704);
+        assert_eq!(mode("PRIVATE.TXT"), 0o600);
+        // Without an extended attribute record, the file keeps the default permissions
+        let default = fs::metadata(output.path().join("BIN")).unwrap();
+        assert!(default.permissions().mode() & 0o700 == 0o700);
+        assert_eq!(fs::read(output.path().join("PLAIN.TXT")).unwrap(), b"plain");
+    }
+}
@@ -1,4 +1,9 @@
-use std::path::PathBuf;
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{attribute::ExtendedAttributeRecord, directory::Interleave, error::IsoError};
 
 #[derive(Debug, Clone)]
 pub enum FileData {
@@ -9,25 +14,47 @@ pub enum FileData {
 }
 
 impl FileData {
-    pub fn get_data(&self) -> Vec<u8> {
+    pub fn get_data(&self) -> Result<Vec<u8>, IsoError> {
         match self {
-            Self::Data(data) => data.clone(),
-            Self::File(path) => std::fs::read(path).unwrap(),
-            Self::Directory(_) => panic!("Cannot get data from a directory"),
+            Self::Data(data) => Ok(data.clone()),
+            Self::File(path) => Ok(std::fs::read(path)?),
+            Self::Directory(_) => Err(IsoError::InvalidInput("directories don't have data")),
         }
     }
 
+    /// Opens the data for reading, without reading all of it into memory
+    pub fn reader(&self) -> Result<Box<dyn Read + '_>, IsoError> {
+        match self {
+            Self::Data(data) => Ok(Box::new(data.as_slice())),
+            Self::File(path) => Ok(Box::new(std::fs::File::open(path)?)),
+            Self::Directory(_) => Err(IsoError::InvalidInput("directories don't have data")),
+        }
+    }
+
+    /// The size of the data in bytes, without reading it
+    pub fn size(&self) -> Result<u64, IsoError> {
+        match self {
+            Self::Data(data) => Ok(data.len() as u64),
+            Self::File(path) => Ok(std::fs::metadata(path)?.len()),
+            Self::Directory(_) => Err(IsoError::InvalidInput("directories don't have data")),
+        }
+    }
+
+    /// The children of a directory, files don't have any
     pub fn get_children(&self) -> Vec<String> {
         match self {
             Self::Directory(children) => children.clone(),
-            _ => panic!("Cannot get children of a file"),
+            _ => Vec::new(),
         }
     }
 
-    pub fn add_child(&mut self, child: String) {
+    pub fn add_child(&mut self, child: String) -> Result<(), IsoError> {
         match self {
-            Self::Directory(children) => children.push(child),
-            _ => panic!("Cannot add child to a file"),
+            Self::Directory(children) => {
+                children.push(child);
+                Ok(())
+            }
+            _ => Err(IsoError::InvalidInput("only directories can have children")),
         }
     }
 }
@@ -36,6 +63,14 @@ impl FileData {
 pub struct File {
     pub path: String,
     pub data: FileData,
+    /// The layout to record the file with, see [`FileInput::set_interleave`]
+    pub interleave: Option<Interleave>,
+    /// The extended attribute record to record before the data, see
+    /// [`FileInput::set_extended_attributes`]
+    pub extended_attributes: Option<ExtendedAttributeRecord>,
+    /// The data of the associated file to record with the same identifier, see
+    /// [`FileInput::set_associated`]
+    pub associated: Option<FileData>,
 }
 
 impl File {
@@ -43,7 +78,10 @@ impl File {
         matches!(self.data, FileData::Directory(_))
     }
 
-    pub fn get_data(&self) -> Vec<u8> {
+    pub fn get_data(&self) -> Result<Vec<u8>, IsoError> {
+        if self.is_directory() {
+            return Err(IsoError::IsADirectory(self.path.clone()));
+        }
         self.data.get_data()
     }
 
@@ -51,8 +89,8 @@ impl File {
         self.data.get_children()
     }
 
-    pub fn add_child(&mut self, child: String) {
-        self.data.add_child(child);
+    pub fn add_child(&mut self, child: String) -> Result<(), IsoError> {
+        self.data.add_child(child)
     }
 }
 
@@ -67,11 +105,18 @@ pub struct FileInput {
 }
 
 impl FileInput {
-    pub fn from_fs(root: PathBuf) -> Result<FileInput, std::io::Error> {
-        assert!(root.is_dir(), "File {} is not a directory", root.display());
+    pub fn from_fs(root: PathBuf) -> Result<FileInput, IsoError> {
+        if !root.is_dir() {
+            return Err(IsoError::InvalidInput(
+                "the root of the files is not a directory",
+            ));
+        }
         let mut files = vec![File {
             path: "".to_string(),
             data: FileData::Directory(Vec::new()),
+            interleave: None,
+            extended_attributes: None,
+            associated: None,
         }];
         let mut stack = vec![root.clone()];
         while let Some(dir) = stack.pop() {
@@ -79,19 +124,16 @@ impl FileInput {
             let children = std::fs::read_dir(&dir)?;
             for child in children {
                 let child = child?;
-                childrens.push(child.file_name().to_str().unwrap().to_string());
-
-                let name = child
-                    .path()
-                    .strip_prefix(&root)
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_string();
+                childrens.push(path_to_string(Path::new(&child.file_name()))?);
+
+                let name = path_to_string(child.path().strip_prefix(&root).unwrap())?;
                 if child.file_type()?.is_dir() {
                     files.push(File {
                         path: name,
                         data: FileData::Directory(Vec::new()),
+                        interleave: None,
+                        extended_attributes: None,
+                        associated: None,
                     });
                     stack.push(child.path());
                     continue;
@@ -101,10 +143,13 @@ impl FileInput {
                 files.push(File {
                     path: name,
                     data: FileData::File(path),
+                    interleave: None,
+                    extended_attributes: None,
+                    associated: None,
                 });
             }
             let dir = dir.strip_prefix(&root).unwrap();
-            let dir_name = dir.to_str().unwrap();
+            let dir_name = path_to_string(dir)?;
             let dir = files.iter_mut().find(|f| f.path == dir_name).unwrap();
             dir.data = FileData::Directory(childrens);
         }
@@ -128,11 +173,12 @@ impl FileInput {
         (dirs, self.files)
     }
 
-    pub fn append(&mut self, file: File) {
+    pub fn append(&mut self, file: File) -> Result<(), IsoError> {
         // TODO: Support adding nested files
         let parent = self.get_mut("").unwrap();
-        parent.add_child(file.path.clone());
+        parent.add_child(file.path.clone())?;
         self.files.push(file);
+        Ok(())
     }
 
     pub fn get(&self, name: &str) -> Option<&File> {
@@ -146,6 +192,67 @@ impl FileInput {
     pub fn contains(&self, name: &str) -> bool {
         self.get(name).is_some()
     }
+
+    /// Records the file at the given path interleaved with gaps, files are contiguous otherwise.
+    ///
+    /// Interleaved files have to fit in a single extent, and can't be used as a boot image.
+    pub fn set_interleave(&mut self, path: &str, interleave: Interleave) -> Result<(), IsoError> {
+        let file = self
+            .get_mut(path)
+            .ok_or_else(|| IsoError::NotFound(path.to_string()))?;
+        if file.is_directory() {
+            return Err(IsoError::IsADirectory(path.to_string()));
+        }
+        file.interleave = Some(interleave);
+        Ok(())
+    }
+
+    /// Records the given extended attribute record in the sectors before the data of the file at
+    /// the given path.
+    ///
+    /// The extended attribute record can't be added to a boot image.
+    pub fn set_extended_attributes(
+        &mut self,
+        path: &str,
+        record: ExtendedAttributeRecord,
+    ) -> Result<(), IsoError> {
+        let file = self
+            .get_mut(path)
+            .ok_or_else(|| IsoError::NotFound(path.to_string()))?;
+        if file.is_directory() {
+            return Err(IsoError::IsADirectory(path.to_string()));
+        }
+        file.extended_attributes = Some(record);
+        Ok(())
+    }
+
+    /// Records an associated file with the same identifier as the file at the given path, such
+    /// as the resource fork of a Macintosh file.
+    ///
+    /// The associated file is recorded before the file in its directory, which is how readers
+    /// tell them apart, along with [`crate::directory::FileFlags::ASSOCIATED_FILE`].
+    pub fn set_associated(&mut self, path: &str, data: FileData) -> Result<(), IsoError> {
+        if matches!(data, FileData::Directory(_)) {
+            return Err(IsoError::InvalidInput(
+                "associated files can't be directories",
+            ));
+        }
+        let file = self
+            .get_mut(path)
+            .ok_or_else(|| IsoError::NotFound(path.to_string()))?;
+        if file.is_directory() {
+            return Err(IsoError::IsADirectory(path.to_string()));
+        }
+        file.associated = Some(data);
+        Ok(())
+    }
+}
+
+/// Converts a host path to a path in the image, which must be valid UTF-8
+fn path_to_string(path: &Path) -> Result<String, IsoError> {
+    path.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| IsoError::InvalidName(path.to_string_lossy().into_owned()))
 }
 
 #[cfg(test)]
@@ -166,18 +273,28 @@ mod tests {
         let efi_cfg = efi_dir.join("BOOTX64.EFI");
         fs::write(&efi_cfg, "test2").unwrap();
 
-        let fs = FileInput::from_fs(root.into_path()).unwrap();
-        println!("fs: {:#?}", fs);
-        todo!();
+        let fs = FileInput::from_fs(root_path.to_path_buf()).unwrap();
         let grub_cfg = fs
             .files
             .iter()
             .find(|f| f.path == "BOOT/GRUB/GRUB.CFG")
             .unwrap();
+        assert_eq!(grub_cfg.get_data().unwrap(), b"test");
         let efi_cfg = fs
             .files
             .iter()
             .find(|f| f.path == "EFI/BOOTX64.EFI")
             .unwrap();
+        assert_eq!(efi_cfg.get_data().unwrap(), b"test2");
+
+        let mut root_children = fs.get("").unwrap().get_children();
+        root_children.sort();
+        assert_eq!(root_children, vec!["BOOT", "EFI"]);
+        assert_eq!(fs.get("BOOT").unwrap().get_children(), vec!["GRUB"]);
+        assert!(matches!(
+            fs.get("BOOT").unwrap().get_data(),
+            Err(IsoError::IsADirectory(_))
+        ));
+        assert!(FileInput::from_fs(boot_dir.join("GRUB.CFG")).is_err());
     }
 }
@@ -1,153 +1,448 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 use std::{
     collections::BTreeMap,
     fmt::Debug,
     io::{Read, Seek, SeekFrom, Write},
+    ops::{Deref, DerefMut},
 };
 
+#[cfg(feature = "std")]
 use boot::{BootCatalogue, BootInfoTable};
-use directory::{DirectoryRecord, DirectoryRecordHeader, DirectoryRef, FileFlags};
+#[cfg(feature = "std")]
+use directory::{
+    DirectoryRecord, DirectoryRecordHeader, DirectoryRef, FileFlags, Interleave, Metadata,
+};
+#[cfg(feature = "std")]
+use error::IsoError;
+#[cfg(feature = "std")]
 use file::FileInput;
-use path::PathTableEntry;
-use types::{Endian, IsoStringFile, U16, U32};
+#[cfg(feature = "std")]
+use partition::OffsetReader;
+#[cfg(feature = "std")]
+use path::{DirectoryTree, PathTableEntry, PathTableKind};
+#[cfg(feature = "std")]
+use reader::IsoReader;
+#[cfg(feature = "std")]
+use types::{Endian, U32};
+#[cfg(feature = "std")]
 use volume::{
     BootRecordVolumeDescriptor, PrimaryVolumeDescriptor, VolumeDescriptor, VolumeDescriptorList,
+    VolumePartitionDescriptor,
 };
+#[cfg(feature = "std")]
+use walk::Walk;
 
+#[cfg(feature = "async")]
+pub mod async_reader;
+pub mod attribute;
+pub mod block;
 pub mod boot;
+#[cfg(feature = "std")]
+pub mod cache;
 pub mod directory;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod extract;
+#[cfg(feature = "std")]
 pub mod file;
+#[cfg(feature = "std")]
+pub mod partition;
 pub mod path;
+#[cfg(feature = "std")]
+pub mod read_at;
+#[cfg(feature = "std")]
+pub mod reader;
+#[cfg(feature = "std")]
+pub mod recover;
+#[cfg(feature = "std")]
+pub mod session;
+pub mod slice;
 pub mod types;
 pub mod volume;
+#[cfg(feature = "std")]
+pub mod walk;
 
+#[cfg(feature = "std")]
 #[derive(Debug, Clone)]
 pub struct FormatOptions {
     pub files: FileInput,
     pub protective_mbr: bool,
     pub el_torito: Option<ElToritoOptions>,
+    /// The partitions to reserve after the file data, each one is declared with a volume
+    /// partition descriptor
+    pub partitions: Vec<VolumePartitionOptions>,
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct VolumePartitionOptions {
+    /// The volume partition identifier, which is limited to 32 d-characters
+    pub identifier: String,
+    /// The size of the partition in sectors
+    pub sectors: u32,
+    /// The initial contents of the partition, the rest of it is zeroed
+    pub data: Vec<u8>,
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug, Clone)]
 pub struct ElToritoOptions {
     // Emulating is not supported
     pub load_size: u16,
-    // The path to the boot image,
-    // Currently on root directory is supported
+    /// The path to the boot image, relative to the root directory
     pub boot_image_path: String,
     /// Whether to write the boot info table, for bootloaders like:
     /// GRUB, LIMINE, SYSLINUX
     pub boot_info_table: bool,
 }
 
+#[cfg(feature = "std")]
 pub trait ReadWriteSeek: Read + Write + Seek {}
+#[cfg(feature = "std")]
 impl<T: Read + Write + Seek> ReadWriteSeek for T {}
 
-fn to_sectors_ceil(size: usize) -> usize {
-    (size + 2047) / 2048
-}
-
+/// A read-write ISO image.
+///
+/// All of the read APIs live on [`IsoReader`], which this dereferences to, so the
+/// [`ReadWriteSeek`] bound is only required for formatting and modifying images.
+///
+/// The image doesn't have to start at the start of the data, see [`IsoImage::new_at`].
+#[cfg(feature = "std")]
 #[derive(Debug)]
 pub struct IsoImage<'a, T: ReadWriteSeek> {
-    data: &'a mut T,
-    size: u64,
-
-    volume_descriptors: VolumeDescriptorList,
-    root_directory: DirectoryRef,
-    path_table: PathTableRef,
+    reader: IsoReader<OffsetReader<&'a mut T>>,
 }
 
-pub struct IsoDirectory<'a, T: ReadWriteSeek> {
+#[cfg(feature = "std")]
+pub struct IsoDirectory<'a, T: Read + Seek> {
     reader: &'a mut T,
     directory: DirectoryRef,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
 pub struct PathTableRef {
     lpath_table_offset: u64,
     mpath_table_offset: u64,
+    /// The LBA of the optional copy of the type L path table, or 0 if there is none
+    opt_lpath_table_offset: u64,
+    /// The LBA of the optional copy of the type M path table, or 0 if there is none
+    opt_mpath_table_offset: u64,
     size: u64,
 }
 
-pub struct IsoPathTable<'a, T: ReadWriteSeek> {
+impl PathTableRef {
+    fn new(pvd: &volume::PrimaryVolumeDescriptor) -> Self {
+        use types::Endian;
+        Self {
+            lpath_table_offset: pvd.type_l_path_table.get() as u64,
+            mpath_table_offset: pvd.type_m_path_table.get() as u64,
+            opt_lpath_table_offset: pvd.opt_type_l_path_table.get() as u64,
+            opt_mpath_table_offset: pvd.opt_type_m_path_table.get() as u64,
+            size: pvd.path_table_size.read() as u64,
+        }
+    }
+
+    /// The LBA of the given copy of the path table, or `None` if the image doesn't have it
+    #[cfg(feature = "std")]
+    fn offset(&self, kind: PathTableKind) -> Option<u64> {
+        match kind {
+            PathTableKind::TypeL => Some(self.lpath_table_offset),
+            PathTableKind::TypeM => Some(self.mpath_table_offset),
+            PathTableKind::OptionalTypeL => Some(self.opt_lpath_table_offset),
+            PathTableKind::OptionalTypeM => Some(self.opt_mpath_table_offset),
+        }
+        .filter(|&offset| offset != 0)
+    }
+}
+
+#[cfg(feature = "std")]
+pub struct IsoPathTable<'a, T: Read + Seek> {
     reader: &'a mut T,
     path_table: PathTableRef,
 }
 
-impl<'a, T: ReadWriteSeek> IsoPathTable<'a, T> {
-    pub fn entries(&mut self) -> Result<Vec<PathTableEntry>, std::io::Error> {
-        // TODO: Some sort of strict check that checks both tables?
+/// An iterator over the records of a directory, see [`IsoDirectory::iter`]
+///
+/// The directory is read one sector at a time. Records never cross a sector boundary, so the
+/// zero padding at the end of each sector is skipped.
+#[cfg(feature = "std")]
+pub struct DirectoryEntries<'a, T: Read + Seek> {
+    reader: &'a mut T,
+    directory: DirectoryRef,
+    sector: Vec<u8>,
+    /// The index of the sector currently in the buffer, relative to the start of the directory
+    loaded_sector: Option<u64>,
+    /// The offset of the next record, relative to the start of the directory
+    offset: u64,
+}
+
+/// A handle to a file in the image, see [`IsoReader::open`]
+///
+/// Reads are bounded to the extents of the file, and only ever buffer what the caller asks for,
+/// so large files can be streamed out with [`std::io::copy`].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IsoFile<T: Read + Seek> {
+    reader: T,
+    record: DirectoryRecord,
+    /// The extents of the file, in the order they appear in the file
+    extents: Vec<DirectoryRef>,
+    interleave: Option<Interleave>,
+    len: u64,
+    position: u64,
+}
+
+/// Splits a path into its parent directory and its final component,
+/// ignoring any leading or trailing slashes.
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_matches('/');
+    path.rsplit_once('/').unwrap_or(("", path))
+}
 
-        // We always read from the native endian table
-        let offset = if cfg!(target_endian = "little") {
-            self.path_table.lpath_table_offset
+#[cfg(feature = "std")]
+impl<T: Read + Seek> IsoPathTable<'_, T> {
+    /// Returns the entries of the native endian path table.
+    ///
+    /// This doesn't check the other copies of the path table, see [`IsoPathTable::verify`].
+    pub fn entries(&mut self) -> Result<Vec<PathTableEntry>, IsoError> {
+        let kind = if cfg!(target_endian = "little") {
+            PathTableKind::TypeL
         } else {
-            self.path_table.mpath_table_offset
+            PathTableKind::TypeM
         };
-        self.reader.seek(SeekFrom::Start(offset * 2048))?;
+        Ok(self.read_table(kind)?.unwrap_or_default())
+    }
+
+    /// Returns the entries of the given copy of the path table, or `None` if the image doesn't
+    /// have it
+    pub fn read_table(
+        &mut self,
+        kind: PathTableKind,
+    ) -> Result<Option<Vec<PathTableEntry>>, IsoError> {
         let mut entries = Vec::new();
+        match self.path_table.offset(kind) {
+            Some(offset) => self.read_entries(offset, kind, &mut entries)?,
+            None => return Ok(None),
+        }
+        Ok(Some(entries))
+    }
+
+    /// Checks that every copy of the path table has the same entries.
+    ///
+    /// When the copies disagree, the one that differs from the most others is reported as
+    /// corrupt. If there is no majority, e.g. when there are only the type L and type M tables,
+    /// the copy whose entry doesn't point at a directory is reported, and otherwise the type M
+    /// table, since the type L table is the one most readers use.
+    pub fn verify(&mut self) -> Result<(), IsoError> {
+        let mut tables = Vec::new();
+        for kind in PathTableKind::ALL {
+            let Some(offset) = self.path_table.offset(kind) else {
+                continue;
+            };
+            // A table that can't be parsed is compared up to the entry that failed, but a table
+            // that can't be read at all is an error
+            let mut entries = Vec::new();
+            match self.read_entries(offset, kind, &mut entries) {
+                Ok(()) | Err(IsoError::InvalidData(_) | IsoError::InvalidName(_)) => {}
+                Err(err) => return Err(err),
+            }
+            tables.push((kind, entries));
+        }
+
+        let count = tables.iter().map(|(_, entries)| entries.len()).max();
+        for index in 0..count.unwrap_or(0) {
+            let entries: Vec<Option<&PathTableEntry>> = tables
+                .iter()
+                .map(|(_, entries)| entries.get(index))
+                .collect();
+            let agreeing = |entry: &Option<&PathTableEntry>| {
+                entries.iter().filter(|other| *other == entry).count()
+            };
+            let most = entries.iter().map(agreeing).max().unwrap_or(0);
+            let mut candidates = Vec::new();
+            for entry in &entries {
+                if agreeing(entry) == most && !candidates.contains(entry) {
+                    candidates.push(*entry);
+                }
+            }
+            let expected = match candidates[..] {
+                [entry] => entry,
+                _ => candidates
+                    .iter()
+                    .copied()
+                    .find(|entry| entry.is_some_and(|entry| self.is_directory(entry.extent)))
+                    .unwrap_or(candidates[0]),
+            };
+            if let Some(((kind, _), _)) = tables
+                .iter()
+                .zip(&entries)
+                .rev()
+                .find(|(_, entry)| **entry != expected)
+            {
+                return Err(IsoError::CorruptPathTable {
+                    table: *kind,
+                    entry: index + 1,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn read_entries(
+        &mut self,
+        offset: u64,
+        kind: PathTableKind,
+        entries: &mut Vec<PathTableEntry>,
+    ) -> Result<(), IsoError> {
+        self.reader.seek(SeekFrom::Start(offset * 2048))?;
         let mut idx = 0;
         while idx < self.path_table.size as usize {
-            let entry = PathTableEntry::parse(self.reader, types::EndianType::NativeEndian)?;
+            let entry = PathTableEntry::parse(self.reader, kind.endian())?;
             if entry.length == 0 {
                 break;
             }
             idx += entry.size();
             entries.push(entry);
         }
-        Ok(entries)
+        Ok(())
     }
-}
 
-impl<'a, T: ReadWriteSeek> IsoDirectory<'a, T> {
-    // TODO: Make this private after testing
-    /// Returns a list of all entries in the directory, along with their offset in the directory
-    pub fn entries(&mut self) -> Result<Vec<(u64, DirectoryRecord)>, std::io::Error> {
-        self.reader
-            .seek(SeekFrom::Start(self.directory.offset * 2048))?;
-        // This is the easiest implementation, but it's not the most efficient
-        // because we are storing the entire directory in memory.
-        let mut bytes = vec![0; self.directory.size as usize];
-        self.reader.read_exact(&mut bytes)?;
-        let mut entries = Vec::new();
-        let mut idx = 0;
-        while idx < bytes.len() {
-            let entry = DirectoryRecordHeader::from_bytes(
-                &bytes[idx..idx + size_of::<DirectoryRecordHeader>()],
-            );
-            if entry.len == 0 {
-                break;
+    /// Whether the `.` record at the given LBA points back at itself
+    fn is_directory(&mut self, lba: u32) -> bool {
+        let mut buf = [0; size_of::<DirectoryRecordHeader>()];
+        let read = self
+            .reader
+            .seek(SeekFrom::Start(lba as u64 * 2048))
+            .and_then(|_| self.reader.read_exact(&mut buf));
+        let header = DirectoryRecordHeader::from_bytes(&buf);
+        read.is_ok() && header.is_directory() && header.extent.read() == lba
+    }
+
+    /// Returns the directory hierarchy described by the path table, with every parent resolved
+    pub fn tree(&mut self) -> Result<DirectoryTree, IsoError> {
+        DirectoryTree::from_entries(&self.entries()?)
+    }
+
+    /// Finds the directory at the given path.
+    ///
+    /// This uses the path table to jump straight to the directory extent, instead of walking
+    /// every directory along the path.
+    pub fn find_directory(&mut self, path: &str) -> Result<Option<DirectoryRef>, IsoError> {
+        self.find_directory_matching(path, false)
+    }
+
+    /// Finds the directory at the given path, optionally ignoring ASCII case
+    pub(crate) fn find_directory_matching(
+        &mut self,
+        path: &str,
+        case_insensitive: bool,
+    ) -> Result<Option<DirectoryRef>, IsoError> {
+        let entries = self.entries()?;
+        // Path table indices are 1-based, and the root directory is always the first entry
+        let mut index = 1;
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            let child = entries.iter().enumerate().skip(1).find(|(_, entry)| {
+                entry.parent_index as usize == index
+                    && if case_insensitive {
+                        entry.name.eq_ignore_ascii_case(component)
+                    } else {
+                        entry.name == component
+                    }
+            });
+            match child {
+                Some((idx, _)) => index = idx + 1,
+                None => return Ok(None),
             }
-            let name = IsoStringFile::from_bytes(
-                &bytes[idx + size_of::<DirectoryRecordHeader>()
-                    ..idx
-                        + size_of::<DirectoryRecordHeader>()
-                        + entry.file_identifier_len as usize],
-            );
-            entries.push((
-                idx as u64,
-                DirectoryRecord {
-                    header: *entry,
-                    name,
-                },
-            ));
-            idx += entry.len as usize;
         }
-        Ok(entries)
+        let Some(entry) = entries.get(index - 1) else {
+            return Ok(None);
+        };
+
+        // The path table doesn't store the size of the directory, so we read it from the '.'
+        // entry, which is always the first record of the directory
+        let offset = entry.extent as u64;
+        self.reader.seek(SeekFrom::Start(offset * 2048))?;
+        let mut buf = [0; size_of::<DirectoryRecordHeader>()];
+        self.reader.read_exact(&mut buf)?;
+        let header = DirectoryRecordHeader::from_bytes(&buf);
+        Ok(Some(DirectoryRef {
+            offset,
+            size: header.data_len.read() as u64,
+        }))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, T: Read + Seek> IsoDirectory<'a, T> {
+    /// Recursively walks the directory tree below this directory
+    pub fn walk(self) -> Walk<'a, T> {
+        Walk::new(self.reader, self.directory)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Read + Seek> IsoDirectory<'_, T> {
+    /// Returns a lazy iterator over the entries in the directory, along with their offset in the
+    /// directory
+    pub fn iter(&mut self) -> DirectoryEntries<'_, T> {
+        DirectoryEntries {
+            reader: self.reader,
+            directory: self.directory,
+            sector: vec![0; 2048],
+            loaded_sector: None,
+            offset: 0,
+        }
+    }
+
+    /// Returns a list of all entries in the directory, along with their offset in the directory
+    pub fn entries(&mut self) -> Result<Vec<(u64, DirectoryRecord)>, IsoError> {
+        self.iter().collect()
     }
 
-    pub fn find_directory(
+    /// Finds the entry with the given name in this directory.
+    ///
+    /// Names are matched like [`types::FileIdentifier::matches`], so `README.TXT` finds
+    /// `README.TXT;1`. If there are several versions of a file, the highest one is returned,
+    /// unless the name has a version.
+    ///
+    /// Associated files are never returned, see [`IsoDirectory::find_associated`].
+    pub fn find(&mut self, name: &str) -> Result<Option<DirectoryRecord>, IsoError> {
+        self.find_matching(name, false, false)
+    }
+
+    /// Finds the entry with the given name in this directory, ignoring ASCII case, see
+    /// [`IsoDirectory::find`]
+    pub fn find_ignore_case(&mut self, name: &str) -> Result<Option<DirectoryRecord>, IsoError> {
+        self.find_matching(name, true, false)
+    }
+
+    /// Finds the associated file of the entry with the given name in this directory, such as
+    /// the resource fork of a file on a Macintosh CD-ROM
+    pub fn find_associated(&mut self, name: &str) -> Result<Option<DirectoryRecord>, IsoError> {
+        self.find_matching(name, false, true)
+    }
+
+    pub(crate) fn find_matching(
         &mut self,
         name: &str,
-    ) -> Result<Option<IsoDirectory<T>>, std::io::Error> {
-        let entry = self.entries()?.iter().find_map(|(_offset, entry)| {
-            if entry.name.to_str() == name
-                && FileFlags::from_bits_retain(entry.header.flags).contains(FileFlags::DIRECTORY)
-            {
-                Some(entry.clone())
-            } else {
-                None
-            }
-        });
+        case_insensitive: bool,
+        associated: bool,
+    ) -> Result<Option<DirectoryRecord>, IsoError> {
+        let mut found = None;
+        for entry in self.iter() {
+            let (_offset, entry) = entry?;
+            directory::keep_match(&mut found, entry, name, case_insensitive, associated);
+        }
+        Ok(found)
+    }
+
+    pub fn find_directory(&mut self, name: &str) -> Result<Option<IsoDirectory<'_, T>>, IsoError> {
+        let entry = self.find(name)?.filter(|entry| entry.header.is_directory());
         match entry {
             Some(entry) => Ok(Some(IsoDirectory {
                 reader: self.reader,
@@ -160,32 +455,165 @@ impl<'a, T: ReadWriteSeek> IsoDirectory<'a, T> {
         }
     }
 
-    pub fn read_file(&mut self, name: &str) -> Result<Vec<u8>, std::io::Error> {
-        let entry = self.entries()?.iter().find_map(|(_offset, entry)| {
-            if entry.name.to_str() == name {
-                Some(entry.clone())
-            } else {
-                None
-            }
-        });
-        match entry {
+    pub fn read_file(&mut self, name: &str) -> Result<Vec<u8>, IsoError> {
+        match self.find(name)? {
             Some(entry) => {
-                let mut bytes = vec![0; entry.header.data_len.read() as usize];
-                self.reader
-                    .seek(SeekFrom::Start(entry.header.extent.read() as u64))?;
-                self.reader.read_exact(&mut bytes)?;
+                let mut file = IsoFile::new(&mut *self.reader, entry);
+                let mut bytes = Vec::with_capacity(file.len() as usize);
+                file.read_to_end(&mut bytes)?;
                 Ok(bytes)
             }
+            None => Err(IsoError::NotFound(name.to_string())),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Read + Seek> DirectoryEntries<'_, T> {
+    /// Returns the next entry, with the records of a multi-extent file merged into its first
+    /// record
+    fn next_record(&mut self) -> Result<Option<(u64, DirectoryRecord)>, IsoError> {
+        let Some((offset, mut record)) = self.next_raw_record()? else {
+            return Ok(None);
+        };
+        record.read_continuation(|| {
+            self.next_raw_record()
+                .transpose()
+                .map(|next| next.map(|(_, record)| record))
+        })?;
+        Ok(Some((offset, record)))
+    }
+
+    fn next_raw_record(&mut self) -> Result<Option<(u64, DirectoryRecord)>, IsoError> {
+        while self.offset < self.directory.size {
+            let sector = self.offset / 2048;
+            let start = (self.offset % 2048) as usize;
+            if self.loaded_sector != Some(sector) {
+                self.reader
+                    .seek(SeekFrom::Start((self.directory.offset + sector) * 2048))?;
+                self.reader.read_exact(&mut self.sector)?;
+                self.loaded_sector = Some(sector);
+            }
+
+            if self.sector[start] == 0 {
+                // The rest of the sector is padding, so the next record is in the next sector
+                self.offset = (sector + 1) * 2048;
+                continue;
+            }
+
+            let record = DirectoryRecord::parse(&self.sector[start..])?;
+            let offset = self.offset;
+            self.offset += record.header.len as u64;
+            return Ok(Some((offset, record)));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Read + Seek> Iterator for DirectoryEntries<'_, T> {
+    type Item = Result<(u64, DirectoryRecord), IsoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.next_record();
+        if record.is_err() {
+            // We can't know where the next record starts, so we stop here
+            self.offset = self.directory.size;
+        }
+        record.transpose()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Read + Seek> IsoFile<T> {
+    fn new(reader: T, record: DirectoryRecord) -> Self {
+        let extents = record.extents();
+        let interleave = Interleave::from_header(&record.header);
+        Self {
+            reader,
+            record,
+            len: extents.iter().map(|extent| extent.size).sum(),
+            extents,
+            interleave,
+            position: 0,
+        }
+    }
+
+    pub fn record(&self) -> &DirectoryRecord {
+        &self.record
+    }
+
+    pub fn metadata(&self) -> Metadata {
+        Metadata::new(&self.record, self.extents.clone())
+    }
+
+    /// The size of the file in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Read + Seek> Read for IsoFile<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some((offset, contiguous)) =
+            directory::locate(&self.extents, self.interleave, self.position)
+        else {
+            return Ok(0);
+        };
+        let len = contiguous.min(buf.len() as u64) as usize;
+        // We always seek, because the underlying reader may be shared with other handles
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let read = self.reader.read(&mut buf[..len])?;
+        if read == 0 && len > 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "image ends before the end of the file",
+            ));
+        }
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Read + Seek> Seek for IsoFile<T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
             None => Err(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "File not found",
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
             )),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a, T: ReadWriteSeek> IsoImage<'a, T> {
-    pub fn format_new(data: &'a mut T, mut ops: FormatOptions) -> Result<(), std::io::Error> {
+    pub fn format_new(data: &'a mut T, ops: FormatOptions) -> Result<(), IsoError> {
+        Self::format(data, ops, directory::MAX_EXTENT_SIZE)
+    }
+
+    /// Formats the image, splitting files into extents of at most `max_extent_size` bytes, which
+    /// tests lower so that multi-extent files don't need gigabytes of data
+    pub(crate) fn format(
+        data: &'a mut T,
+        mut ops: FormatOptions,
+        max_extent_size: u64,
+    ) -> Result<(), IsoError> {
         let size_bytes = data.seek(SeekFrom::End(0))?;
         let size_sectors = size_bytes / 2048;
         log::trace!(
@@ -196,7 +624,9 @@ impl<'a, T: ReadWriteSeek> IsoImage<'a, T> {
 
         if ops.protective_mbr {
             data.seek(SeekFrom::Start(0))?;
-            data.write_all(bytemuck::bytes_of(&ProtectiveMBR::new(size_sectors as u32)))?;
+            // The partition covers the rest of the image, in 512 byte blocks after the MBR
+            let blocks = (size_bytes / 512).saturating_sub(1).min(u32::MAX as u64);
+            data.write_all(bytemuck::bytes_of(&ProtectiveMBR::new(blocks as u32)))?;
         }
 
         let mut volume_descriptors = VolumeDescriptorList::empty();
@@ -210,30 +640,63 @@ impl<'a, T: ReadWriteSeek> IsoImage<'a, T> {
             volume_descriptors.push(VolumeDescriptor::BootRecord(
                 BootRecordVolumeDescriptor::new(0),
             ));
-            assert!(
-                ops.files.contains(&el_torito.boot_image_path),
-                "Boot image path not found in files"
-            );
+            let boot_image = ops.files.get(&el_torito.boot_image_path);
+            if boot_image.is_none_or(|file| file.is_directory()) {
+                return Err(IsoError::MissingBootImage(
+                    el_torito.boot_image_path.clone(),
+                ));
+            }
+            if boot_image.is_some_and(|file| file.interleave.is_some()) {
+                return Err(IsoError::InvalidInput(
+                    "the boot image can't be interleaved",
+                ));
+            }
+            if boot_image.is_some_and(|file| file.extended_attributes.is_some()) {
+                return Err(IsoError::InvalidInput(
+                    "the boot image can't have an extended attribute record",
+                ));
+            }
             log::trace!("Appending boot catalogue to file list");
             ops.files.append(file::File {
                 path: "boot.catalog".to_string(),
                 // TODO: We need to make this dynamic
                 data: file::FileData::Data(vec![0; 32 * 4]),
-            });
+                interleave: None,
+                extended_attributes: None,
+                associated: None,
+            })?;
+        }
+
+        for partition in &ops.partitions {
+            if partition.data.len() as u64 > partition.sectors as u64 * 2048 {
+                return Err(IsoError::InvalidInput(
+                    "partition data doesn't fit in the partition",
+                ));
+            }
+            // The location is only known once the files are written
+            volume_descriptors.push(VolumeDescriptor::Partition(VolumePartitionDescriptor::new(
+                &partition.identifier,
+                0,
+                partition.sectors,
+            )?));
         }
 
         let mut current_index: u64 = 16 * 2048;
         current_index += volume_descriptors.size_required() as u64;
-        data.seek(SeekFrom::Start(current_index as u64))?;
+        data.seek(SeekFrom::Start(current_index))?;
 
-        let mut file_writer = FileWriter::new(data, ops.files);
+        let mut file_writer = FileWriter::new(data, ops.files, max_extent_size);
         let (root_dir, path_table) = file_writer.write()?;
+        // Looking up the boot image moves the cursor, so we keep track of where the data ends
+        let mut data_end = Self::align(data)?;
 
         {
             log::trace!("Updating primary volume descriptor");
-            let pvd = volume_descriptors.primary_mut();
-            pvd.dir_record.header.extent.write(root_dir.offset as u32);
-            pvd.dir_record.header.data_len.write(root_dir.size as u32);
+            let pvd = volume_descriptors
+                .primary_mut()
+                .ok_or(IsoError::MissingPrimaryDescriptor)?;
+            pvd.dir_record.header =
+                DirectoryRecord::new(&[0x00], root_dir, FileFlags::DIRECTORY).header;
             pvd.path_table_size.write(path_table.size as u32);
             pvd.type_l_path_table.set(path_table.offset as u32);
             pvd.type_m_path_table
@@ -241,26 +704,32 @@ impl<'a, T: ReadWriteSeek> IsoImage<'a, T> {
         }
 
         if let Some(ops) = ops.el_torito {
-            // TODO: If we support nested files, we need to find them from the Path table, and not
-            // the root directory
-            let mut root_dir = IsoDirectory {
-                reader: data,
-                directory: root_dir.clone(),
+            let path_table = PathTableRef {
+                lpath_table_offset: path_table.offset,
+                mpath_table_offset: path_table.offset + path_table.size / 2048,
+                size: path_table.size,
+                ..Default::default()
             };
-            let (_, file) = root_dir
-                .entries()?
-                .iter()
-                .find(|(_idx, e)| e.name.to_str() == ops.boot_image_path.as_str())
-                .expect("Could not find the boot image path in ISO filesystem")
-                .clone();
-            let (_, catalog_file) = root_dir
-                .entries()?
-                .iter()
-                .find(|(_idx, e)| e.name.to_str() == "boot.catalog")
-                .expect("Could not find the boot catalogue in ISO filesystem")
-                .clone();
-
-            let current_index = Self::align(data)?;
+            let missing_boot_image = || IsoError::MissingBootImage(ops.boot_image_path.clone());
+            let (boot_dir, boot_name) = split_path(&ops.boot_image_path);
+            let boot_dir = IsoPathTable {
+                reader: data,
+                path_table,
+            }
+            .find_directory(boot_dir)?
+            .ok_or_else(missing_boot_image)?;
+            let file = IsoDirectory {
+                reader: data,
+                directory: boot_dir,
+            }
+            .find(boot_name)?
+            .ok_or_else(missing_boot_image)?;
+            let catalog_file = IsoDirectory {
+                reader: data,
+                directory: root_dir,
+            }
+            .find("boot.catalog")?
+            .ok_or(IsoError::NotFound("boot.catalog".to_string()))?;
 
             let boot_image_lba = file.header.extent.read();
 
@@ -287,13 +756,13 @@ impl<'a, T: ReadWriteSeek> IsoImage<'a, T> {
                 data.write_all(bytemuck::bytes_of(&table))?;
             }
 
-            // We need to seek to the file to update the boot info table
-            data.seek(SeekFrom::Start(current_index))?;
+            // The catalogue is placed after all of the file data
+            data.seek(SeekFrom::Start(data_end))?;
 
             let catalogue_start = Self::align(data)? / 2048;
             volume_descriptors
                 .boot_record_mut()
-                .unwrap()
+                .ok_or(IsoError::InvalidData("boot record not found"))?
                 .catalog_ptr
                 .set(catalogue_start as u32);
             // TODO: Allow specification of segment
@@ -304,14 +773,37 @@ impl<'a, T: ReadWriteSeek> IsoImage<'a, T> {
                 boot_image_lba,
             );
             catalog.write(data)?;
-            Self::align(data)?;
+            data_end = Self::align(data)?;
 
             data.seek(SeekFrom::Start(
                 catalog_file.header.extent.read() as u64 * 2048,
             ))?;
-            assert!(catalog_file.header.data_len.read() as usize >= catalog.size());
+            if (catalog_file.header.data_len.read() as usize) < catalog.size() {
+                return Err(IsoError::InvalidInput(
+                    "boot catalogue doesn't fit in the space reserved for it",
+                ));
+            }
             catalog.write(data)?;
         }
+
+        data.seek(SeekFrom::Start(data_end))?;
+        for (partition, descriptor) in ops
+            .partitions
+            .iter()
+            .zip(volume_descriptors.partitions_mut())
+        {
+            let location = Self::current_sector(data)? as u32;
+            if location as u64 + partition.sectors as u64 > size_sectors {
+                return Err(IsoError::InvalidInput(
+                    "the image is too small for its partitions",
+                ));
+            }
+            descriptor.volume_partition_location.write(location);
+            data.write_all(&partition.data)?;
+            // The rest is zeroed without allocating the whole partition
+            let padding = partition.sectors as u64 * 2048 - partition.data.len() as u64;
+            std::io::copy(&mut std::io::repeat(0).take(padding), data)?;
+        }
         Self::align(data)?;
 
         data.seek(SeekFrom::Start(16 * 2048))?;
@@ -320,139 +812,182 @@ impl<'a, T: ReadWriteSeek> IsoImage<'a, T> {
         Ok(())
     }
 
-    pub fn new(data: &'a mut T) -> Result<Self, std::io::Error> {
-        data.seek(SeekFrom::Start(16 * 2048))?;
-        let volume_descriptors = VolumeDescriptorList::parse(data)?;
-        let size = data.seek(SeekFrom::End(0))?;
-
-        let pvd = volume_descriptors.primary();
-        if let Some(boot) = volume_descriptors.boot_record() {
-            data.seek(SeekFrom::Start(boot.catalog_ptr.get() as u64 * 2048))?;
-            let _catalogue = BootCatalogue::parse(data)?;
-            // At the moment we dont support anything with a boot catalogue
-        }
-
-        let root_entry = pvd.dir_record;
-        let root_directory = DirectoryRef {
-            offset: root_entry.header.extent.read() as u64,
-            size: root_entry.header.data_len.read() as u64,
-        };
-
-        let path_table = PathTableRef {
-            lpath_table_offset: pvd.type_l_path_table.get() as u64,
-            mpath_table_offset: pvd.type_m_path_table.get() as u64,
-            size: pvd.path_table_size.read() as u64,
-        };
+    /// Opens the image through the volume descriptor set at LBA 16, see [`IsoReader::new`]
+    pub fn new(data: &'a mut T) -> Result<Self, IsoError> {
+        let len = data.seek(SeekFrom::End(0))?;
+        Self::new_at(data, 0, len)
+    }
 
+    /// Opens an image which takes up the `len` bytes of the data starting at the given byte
+    /// offset, e.g. one inside a partition found with [`partition::find_iso_partitions`]
+    pub fn new_at(data: &'a mut T, offset: u64, len: u64) -> Result<Self, IsoError> {
         Ok(Self {
-            data,
-            size,
-
-            volume_descriptors,
-            root_directory,
-            path_table,
+            reader: IsoReader::new(OffsetReader::new(data, offset, len))?,
         })
     }
 
-    pub fn root_directory(&mut self) -> IsoDirectory<T> {
-        IsoDirectory {
-            reader: &mut self.data,
-            directory: self.root_directory,
-        }
+    fn current_sector(data: &mut T) -> Result<usize, IsoError> {
+        let seek = data.stream_position()?;
+        debug_assert!(seek.is_multiple_of(2048), "Seek must be a multiple of 2048");
+        Ok((seek / 2048) as usize)
     }
 
-    pub fn path_table(&mut self) -> IsoPathTable<T> {
-        IsoPathTable {
-            reader: &mut self.data,
-            path_table: self.path_table,
-        }
+    fn align(data: &mut T) -> Result<u64, IsoError> {
+        let current_seek = data.stream_position()?;
+        let padded_end = (current_seek + 2047) & !2047;
+        data.seek(std::io::SeekFrom::Start(padded_end))?;
+        Ok(padded_end)
     }
+}
 
-    fn current_sector(data: &mut T) -> usize {
-        let seek = data.seek(std::io::SeekFrom::Current(0)).unwrap();
-        assert!(seek % 2048 == 0, "Seek must be a multiple of 2048");
-        (seek / 2048) as usize
+#[cfg(feature = "std")]
+impl<'a, T: ReadWriteSeek> Deref for IsoImage<'a, T> {
+    type Target = IsoReader<OffsetReader<&'a mut T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.reader
     }
+}
 
-    fn align(data: &mut T) -> Result<u64, std::io::Error> {
-        let current_seek = data.seek(std::io::SeekFrom::Current(0))?;
-        let padded_end = (current_seek + 2047) & !2047;
-        data.seek(std::io::SeekFrom::Start(padded_end))?;
-        Ok(padded_end)
+#[cfg(feature = "std")]
+impl<T: ReadWriteSeek> DerefMut for IsoImage<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.reader
     }
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug)]
 struct FileWriter<'a, W: ReadWriteSeek> {
     writer: &'a mut W,
 
     dirs: Vec<file::File>,
     files: Vec<file::File>,
+    /// The index of each file in `files` by its path
+    file_indices: BTreeMap<String, usize>,
 
     /// The first element is whether the file is a directory
     written_files: BTreeMap<String, (bool, DirectoryRef)>,
+    /// The data of the associated files, by the path of their file
+    written_associated: BTreeMap<String, DirectoryRef>,
+    /// The size files are split into extents at, see [`DirectoryRecord::new_multi_extent`]
+    max_extent_size: u64,
 }
 
+#[cfg(feature = "std")]
 impl<'a, W: ReadWriteSeek> FileWriter<'a, W> {
-    pub fn new(writer: &'a mut W, files: FileInput) -> Self {
+    pub fn new(writer: &'a mut W, files: FileInput, max_extent_size: u64) -> Self {
         log::trace!("Started writing files");
         let (mut dirs, mut files) = files.split();
 
         log::trace!("Sorting directories by depth");
         Self::sort_by_depth(&mut dirs);
         Self::sort_by_depth(&mut files);
+        let file_indices = files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| (file.path.clone(), index))
+            .collect();
 
         Self {
             writer,
 
             dirs,
             files,
+            file_indices,
 
             written_files: BTreeMap::new(),
+            written_associated: BTreeMap::new(),
+            max_extent_size,
         }
     }
 
-    /// Sorts the files by their depth in the directory tree
-    /// Files with higher depth are written first
-    fn sort_by_depth(files: &mut Vec<file::File>) {
-        files.sort_by(|a, b| {
-            let a_depth = a.path.split('/').count();
-            let b_depth = b.path.split('/').count();
-            a_depth.cmp(&b_depth)
+    /// Sorts the files by their depth in the directory tree, with the root directory first.
+    /// Files at the same depth are ordered by their path components, so that children are in the
+    /// same order as their parents, which is the order the path table requires.
+    fn sort_by_depth(files: &mut [file::File]) {
+        files.sort_by_cached_key(|file| {
+            let components: Vec<String> = file
+                .path
+                .split('/')
+                .filter(|c| !c.is_empty())
+                .map(str::to_string)
+                .collect();
+            (components.len(), components)
         });
     }
 
+    /// The file at the given path, `None` for directories
+    fn file(&self, path: &str) -> Option<&file::File> {
+        self.file_indices.get(path).map(|&index| &self.files[index])
+    }
+
     /// Writes the file data, directory data, and the path table to the given writer, returning a
     /// tuple containing the root directory and the path table.
-    pub fn write(&mut self) -> Result<(DirectoryRef, DirectoryRef), std::io::Error> {
+    pub fn write(&mut self) -> Result<(DirectoryRef, DirectoryRef), IsoError> {
         self.write_file_data()?;
         let root_dir = self.write_directory_data()?;
         let path_table = self.write_path_table(&root_dir)?;
         Ok((root_dir, path_table))
     }
 
-    fn write_file_data(&mut self) -> Result<(), std::io::Error> {
+    fn write_file_data(&mut self) -> Result<(), IsoError> {
         log::trace!("Started writing file data");
+        // Interleaved files are recorded in a single extent, so they are checked before anything
+        // is written
+        for file in self.files.iter().filter(|file| file.interleave.is_some()) {
+            if file.data.size()? > self.max_extent_size {
+                return Err(IsoError::InvalidInput(
+                    "interleaved files must fit in a single extent",
+                ));
+            }
+        }
+
         for file in &self.files {
-            let data = file.data.get_data();
-            //let size_aligned = (data.len() + 2047) & !2047;
-            self.written_files.insert(
-                file.path.clone(),
-                (
-                    false,
-                    DirectoryRef {
-                        offset: IsoImage::current_sector(self.writer) as u64,
-                        size: data.len() as u64,
-                    },
-                ),
-            );
-            self.writer.write_all(&data)?;
+            if let Some(record) = &file.extended_attributes {
+                self.writer.write_all(&record.to_bytes()?)?;
+            }
+            let offset = IsoImage::current_sector(self.writer)? as u64;
+            // The data is streamed, so large files don't have to fit in memory
+            let mut data = file.data.reader()?;
+            let size = match file.interleave {
+                Some(interleave) => {
+                    let unit_len = interleave.file_unit_size as u64 * 2048;
+                    let gap = vec![0; interleave.gap_size as usize * 2048];
+                    let mut unit = Vec::with_capacity(unit_len as usize);
+                    let mut size = 0;
+                    loop {
+                        unit.clear();
+                        (&mut data).take(unit_len).read_to_end(&mut unit)?;
+                        if unit.is_empty() {
+                            break;
+                        }
+                        if size > 0 {
+                            self.writer.write_all(&gap)?;
+                        }
+                        self.writer.write_all(&unit)?;
+                        size += unit.len() as u64;
+                    }
+                    size
+                }
+                None => std::io::copy(&mut data, self.writer)?,
+            };
+            self.written_files
+                .insert(file.path.clone(), (false, DirectoryRef { offset, size }));
             IsoImage::align(self.writer)?;
+
+            if let Some(associated) = &file.associated {
+                let offset = IsoImage::current_sector(self.writer)? as u64;
+                let size = std::io::copy(&mut associated.reader()?, self.writer)?;
+                self.written_associated
+                    .insert(file.path.clone(), DirectoryRef { offset, size });
+                IsoImage::align(self.writer)?;
+            }
         }
         Ok(())
     }
 
-    fn write_directory_data(&mut self) -> Result<DirectoryRef, std::io::Error> {
+    fn write_directory_data(&mut self) -> Result<DirectoryRef, IsoError> {
         log::trace!("Started writing directory data");
         let current_dir_ent =
             DirectoryRecord::new(&[0x00], DirectoryRef::default(), FileFlags::DIRECTORY);
@@ -460,8 +995,8 @@ impl<'a, W: ReadWriteSeek> FileWriter<'a, W> {
             DirectoryRecord::new(&[0x01], DirectoryRef::default(), FileFlags::DIRECTORY);
 
         // In the first pass, we just write all of the directories from the leaves
-        for file in &self.dirs {
-            let start_sector = IsoImage::current_sector(self.writer);
+        for file in self.dirs.iter().rev() {
+            let start_sector = IsoImage::current_sector(self.writer)?;
             // We can just leave these as default, we modify them in a second pass
             current_dir_ent.write(self.writer)?;
             parent_dir_ent.write(self.writer)?;
@@ -473,7 +1008,7 @@ impl<'a, W: ReadWriteSeek> FileWriter<'a, W> {
                     format!("{}/{}", file.path, entry)
                 };
                 log::trace!("Processing directory record for {}", fullname);
-                let stem = entry.split('/').last().unwrap_or(&entry);
+                let stem = entry.split('/').next_back().unwrap_or(&entry);
                 let (is_dir, file_ref) = self.written_files.get(&fullname).unwrap();
                 let flags = if *is_dir {
                     FileFlags::DIRECTORY
@@ -481,7 +1016,42 @@ impl<'a, W: ReadWriteSeek> FileWriter<'a, W> {
                     FileFlags::empty()
                 };
                 log::trace!("Writing directory record for {}", fullname);
-                DirectoryRecord::new(stem.as_bytes(), *file_ref, flags).write(self.writer)?;
+                let mut records = DirectoryRecord::new_multi_extent(
+                    stem.as_bytes(),
+                    *file_ref,
+                    flags,
+                    self.max_extent_size,
+                )?;
+                let file = self.file(&fullname);
+                if let Some(interleave) = file.and_then(|file| file.interleave) {
+                    // Interleaved files always fit in a single record
+                    records[0].header.file_unit_size = interleave.file_unit_size;
+                    records[0].header.interleave_gap_size = interleave.gap_size;
+                }
+                if let Some(record) = file.and_then(|file| file.extended_attributes.as_ref()) {
+                    // The extended attribute record is only recorded before the first extent
+                    record.attach(&mut records[0].header);
+                }
+                if let Some(associated) = self.written_associated.get(&fullname) {
+                    // The associated file comes first, see ECMA-119 9.3
+                    let mut associated = DirectoryRecord::new_multi_extent(
+                        stem.as_bytes(),
+                        *associated,
+                        FileFlags::ASSOCIATED_FILE,
+                        self.max_extent_size,
+                    )?;
+                    associated.append(&mut records);
+                    records = associated;
+                }
+                for record in records {
+                    // Directory records can't cross a sector boundary, so we pad to the next
+                    // sector
+                    let position = self.writer.stream_position()?;
+                    if position % 2048 + record.header.len as u64 > 2048 {
+                        IsoImage::align(self.writer)?;
+                    }
+                    record.write(self.writer)?;
+                }
             }
 
             let end = IsoImage::align(self.writer)?;
@@ -493,16 +1063,15 @@ impl<'a, W: ReadWriteSeek> FileWriter<'a, W> {
                 .insert(file.path.clone(), (true, directory_ref));
         }
 
-        let root_dir = self.written_files.get("").unwrap().clone();
+        let root_dir = *self.written_files.get("").unwrap();
         let mut stack = vec![(root_dir.1, root_dir.1, "".to_string())];
 
         while let Some((dir_ref, parent_ref, cur_path)) = stack.pop() {
             let start = dir_ref.offset * 2048;
             self.writer.seek(SeekFrom::Start(start))?;
 
-            DirectoryRecord::new(&[0x00], dir_ref, FileFlags::DIRECTORY).write(&mut self.writer)?;
-            DirectoryRecord::new(&[0x01], parent_ref, FileFlags::DIRECTORY)
-                .write(&mut self.writer)?;
+            DirectoryRecord::new(&[0x00], dir_ref, FileFlags::DIRECTORY).write(self.writer)?;
+            DirectoryRecord::new(&[0x01], parent_ref, FileFlags::DIRECTORY).write(self.writer)?;
 
             let mut reader = IsoDirectory {
                 reader: self.writer,
@@ -517,13 +1086,17 @@ impl<'a, W: ReadWriteSeek> FileWriter<'a, W> {
                 if directory.name.bytes() == b"\x00" || directory.name.bytes() == b"\x01" {
                     continue;
                 }
-                let dirname = format!("{}/{}", cur_path, directory.name);
+                let dirname = if cur_path.is_empty() {
+                    directory.name.to_string()
+                } else {
+                    format!("{}/{}", cur_path, directory.name)
+                };
                 let dir_ref_inner = self.written_files.get(dirname.as_str()).unwrap().1;
                 let mut new_entry = directory.clone();
                 new_entry.header.extent.write(dir_ref_inner.offset as u32);
                 new_entry.header.data_len.write(dir_ref_inner.size as u32);
                 self.writer.seek(SeekFrom::Start(start + offset))?;
-                new_entry.write(&mut self.writer)?;
+                new_entry.write(self.writer)?;
                 stack.push((dir_ref_inner, dir_ref, dirname));
             }
         }
@@ -535,25 +1108,22 @@ impl<'a, W: ReadWriteSeek> FileWriter<'a, W> {
         Ok(root_dir.1)
     }
 
-    fn write_path_table(
-        &mut self,
-        root_dir: &DirectoryRef,
-    ) -> Result<DirectoryRef, std::io::Error> {
+    fn write_path_table(&mut self, root_dir: &DirectoryRef) -> Result<DirectoryRef, IsoError> {
         log::trace!("Started writing path table");
-        let start_sector = IsoImage::current_sector(self.writer);
+        let start_sector = IsoImage::current_sector(self.writer)?;
         let mut entries = Vec::new();
-        let mut index = 1; // Root directory is always index 1
         let mut parent_map = std::collections::HashMap::new();
 
         // Write the root directory
         entries.push(PathTableEntry {
             length: 1,
             extended_attr_record: 0,
-            parent_lba: root_dir.offset as u32,
+            extent: root_dir.offset as u32,
             parent_index: 1,
             name: "\0".to_string(),
         });
 
+        // Root directory is always index 1
         parent_map.insert("".to_string(), 1);
 
         for file in &self.dirs {
@@ -565,7 +1135,7 @@ impl<'a, W: ReadWriteSeek> FileWriter<'a, W> {
             let parent_name = file.path.rsplit_once('/').map(|(p, _)| p).unwrap_or("");
 
             let parent_index = *parent_map.get(parent_name).unwrap_or(&1);
-            parent_map.insert(file.path.clone(), index);
+            parent_map.insert(file.path.clone(), entries.len() as u16 + 1);
             let name = file
                 .path
                 .rsplit_once('/')
@@ -576,17 +1146,15 @@ impl<'a, W: ReadWriteSeek> FileWriter<'a, W> {
                 length: name.len() as u8,
                 name: name.to_string(),
                 extended_attr_record: 0,
-                parent_lba: directory_ref.offset as u32,
+                extent: directory_ref.offset as u32,
                 parent_index,
             });
-
-            index += 1;
         }
 
         // Write L-Table (Little-Endian)
         for entry in &entries {
             self.writer
-                .write_all(&entry.to_bytes(types::EndianType::LittleEndian))?;
+                .write_all(&entry.to_bytes(types::EndianType::LittleEndian)?)?;
         }
 
         // Align to sector boundary
@@ -602,16 +1170,17 @@ impl<'a, W: ReadWriteSeek> FileWriter<'a, W> {
         // Write M-Table (Big-Endian)
         for entry in &entries {
             self.writer
-                .write_all(&entry.to_bytes(types::EndianType::BigEndian))?;
+                .write_all(&entry.to_bytes(types::EndianType::BigEndian)?)?;
         }
 
         let mtable_end = IsoImage::align(self.writer)?;
-        assert_eq!(mtable_end - end, path_table_ref.size);
+        debug_assert_eq!(mtable_end - end, path_table_ref.size);
 
         Ok(path_table_ref)
     }
 }
 
+#[cfg(feature = "std")]
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy)]
 struct ProtectiveMBR {
@@ -621,9 +1190,12 @@ struct ProtectiveMBR {
     boot_signature: [u8; 2],   // Must be [0x55, 0xAA]
 }
 
+#[cfg(feature = "std")]
 unsafe impl bytemuck::Zeroable for ProtectiveMBR {}
+#[cfg(feature = "std")]
 unsafe impl bytemuck::Pod for ProtectiveMBR {}
 
+#[cfg(feature = "std")]
 impl ProtectiveMBR {
     pub fn new(total_sectors: u32) -> Self {
         ProtectiveMBR {
@@ -651,3 +1223,312 @@ impl ProtectiveMBR {
         }
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Stages the given files in a temporary directory, which must outlive the input
+    pub fn stage_files(files: &[(&str, &[u8])]) -> (tempfile::TempDir, FileInput) {
+        let root = tempfile::tempdir().unwrap();
+        for (path, data) in files {
+            let path = root.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, data).unwrap();
+        }
+        let input = FileInput::from_fs(root.path().to_path_buf()).unwrap();
+        (root, input)
+    }
+
+    /// The El Torito options of a boot image at the given path
+    pub fn boot_options(boot_image_path: &str) -> ElToritoOptions {
+        ElToritoOptions {
+            load_size: 4,
+            boot_image_path: boot_image_path.to_string(),
+            boot_info_table: false,
+        }
+    }
+
+    /// The options to format the given files with, and nothing else
+    pub fn options(files: FileInput) -> FormatOptions {
+        FormatOptions {
+            files,
+            protective_mbr: false,
+            el_torito: None,
+            partitions: Vec::new(),
+        }
+    }
+
+    /// Formats an image of 256 sectors with the given options, splitting files into extents of
+    /// at most `max_extent_size` bytes
+    pub fn format_options(
+        options: FormatOptions,
+        max_extent_size: u64,
+    ) -> Result<Vec<u8>, IsoError> {
+        let mut image = Cursor::new(vec![0u8; 256 * 2048]);
+        IsoImage::format(&mut image, options, max_extent_size).map(|()| image.into_inner())
+    }
+
+    /// Formats an image of 256 sectors containing the given files and partitions
+    pub fn format_input(
+        files: FileInput,
+        el_torito: Option<ElToritoOptions>,
+        partitions: Vec<VolumePartitionOptions>,
+    ) -> Result<Vec<u8>, IsoError> {
+        format_options(
+            FormatOptions {
+                el_torito,
+                partitions,
+                ..options(files)
+            },
+            directory::MAX_EXTENT_SIZE,
+        )
+    }
+
+    /// Formats an image containing the given files, which are staged in a temporary directory
+    pub fn format_image(files: &[(&str, &[u8])], boot_image_path: Option<&str>) -> Vec<u8> {
+        let (_root, files) = stage_files(files);
+        format_input(files, boot_image_path.map(boot_options), Vec::new()).unwrap()
+    }
+
+    #[test]
+    fn test_missing_boot_image() {
+        let (_root, files) = stage_files(&[("README.TXT", b"hello")]);
+        let result = format_input(files, Some(boot_options("BOOT.BIN")), Vec::new());
+        assert!(matches!(result, Err(IsoError::MissingBootImage(path)) if path == "BOOT.BIN"));
+    }
+
+    #[test]
+    fn test_interleaved_file() {
+        let data: Vec<u8> = (0..3 * 2048 + 100u32).map(|i| (i % 251) as u8).collect();
+        let (_root, mut files) = stage_files(&[("MOVIE.DAT", &data), ("BOOT.BIN", b"boot")]);
+        let interleave = Interleave::new(1, 2).unwrap();
+        files.set_interleave("MOVIE.DAT", interleave).unwrap();
+        files.set_interleave("BOOT.BIN", interleave).unwrap();
+        assert!(Interleave::new(0, 2).is_err());
+
+        assert!(matches!(
+            format_input(files.clone(), Some(boot_options("BOOT.BIN")), Vec::new()),
+            Err(IsoError::InvalidInput(_))
+        ));
+        let bytes = format_input(files, None, Vec::new()).unwrap();
+
+        let mut iso = IsoReader::new(Cursor::new(bytes.clone())).unwrap();
+        let metadata = iso.metadata("/MOVIE.DAT").unwrap();
+        assert_eq!(metadata.interleave(), Some(interleave));
+        assert_eq!(metadata.len(), data.len() as u64);
+        // Every file unit is followed by a gap of two sectors
+        let start = metadata.extents()[0].offset as usize * 2048;
+        assert_eq!(bytes[start..start + 2048], data[..2048]);
+        assert_eq!(bytes[start + 3 * 2048..start + 4 * 2048], data[2048..4096]);
+        assert_eq!(interleave.sectors(data.len() as u64), 10);
+
+        let mut file = iso.open("/MOVIE.DAT").unwrap();
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+        file.seek(SeekFrom::Start(2046)).unwrap();
+        let mut straddling = [0; 4];
+        file.read_exact(&mut straddling).unwrap();
+        assert_eq!(straddling, data[2046..2050]);
+
+        let slice = slice::IsoSlice::new(&bytes).unwrap();
+        assert!(matches!(
+            slice.read("/MOVIE.DAT"),
+            Err(IsoError::InvalidInput(_))
+        ));
+
+        // The file doesn't fit in a single extent, which is found before any file data is written
+        let (_root, mut files) = stage_files(&[("MOVIE.DAT", &data)]);
+        files.set_interleave("MOVIE.DAT", interleave).unwrap();
+        let mut image = Cursor::new(vec![0u8; 256 * 2048]);
+        assert!(matches!(
+            IsoImage::format(&mut image, options(files), 4096),
+            Err(IsoError::InvalidInput(_))
+        ));
+        assert!(image.get_ref()[18 * 2048..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_extended_attributes() {
+        use attribute::{ExtendedAttributeRecord, Permissions};
+
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let (_root, mut files) = stage_files(&[
+            ("LARGE.DAT", &data),
+            ("OPEN.TXT", b"hello"),
+            ("BOOT.BIN", b"boot"),
+        ]);
+        let mut private = ExtendedAttributeRecord::new(
+            1000,
+            100,
+            Permissions::OWNER_READ | Permissions::OWNER_EXECUTE,
+        );
+        private.application_use = b"application".to_vec();
+        files
+            .set_extended_attributes("LARGE.DAT", private.clone())
+            .unwrap();
+        let open = ExtendedAttributeRecord::new(0, 0, Permissions::all());
+        files
+            .set_extended_attributes("OPEN.TXT", open.clone())
+            .unwrap();
+        files
+            .set_extended_attributes("BOOT.BIN", open.clone())
+            .unwrap();
+        assert!(matches!(
+            files.set_extended_attributes("", open.clone()),
+            Err(IsoError::IsADirectory(_))
+        ));
+
+        assert!(matches!(
+            format_input(files.clone(), Some(boot_options("BOOT.BIN")), Vec::new()),
+            Err(IsoError::InvalidInput(_))
+        ));
+        let bytes = format_input(files, None, Vec::new()).unwrap();
+
+        let mut iso = IsoReader::new(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(
+            iso.extended_attributes("/LARGE.DAT").unwrap(),
+            Some(private)
+        );
+        assert_eq!(iso.extended_attributes("/OPEN.TXT").unwrap(), Some(open));
+        assert_eq!(iso.extended_attributes("/").unwrap(), None);
+
+        // Only a record with restricted permissions, an owner or a group is flagged, and the data
+        // starts after the record
+        let large = iso.stat("/LARGE.DAT").unwrap();
+        assert!(
+            FileFlags::from_bits_retain(large.header.flags)
+                .contains(FileFlags::EXTENDED_PERMISSIONS)
+        );
+        assert_eq!(large.header.extended_attr_record, 1);
+        assert_eq!(large.extent().offset, large.header.extent.read() as u64 + 1);
+        let small = iso.stat("/OPEN.TXT").unwrap();
+        assert!(
+            !FileFlags::from_bits_retain(small.header.flags)
+                .contains(FileFlags::EXTENDED_PERMISSIONS)
+        );
+
+        let mut read = Vec::new();
+        iso.open("/LARGE.DAT")
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, data);
+        let slice = slice::IsoSlice::new(&bytes).unwrap();
+        assert_eq!(slice.read("/OPEN.TXT").unwrap(), b"hello");
+        assert_eq!(slice.read("/LARGE.DAT").unwrap(), data);
+    }
+
+    #[test]
+    fn test_associated_files() {
+        let fork: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let (_root, mut files) = stage_files(&[("APP/GAME", b"data fork")]);
+        files
+            .set_associated("APP/GAME", file::FileData::Data(fork.clone()))
+            .unwrap();
+        assert!(matches!(
+            files.set_associated("APP", file::FileData::Data(Vec::new())),
+            Err(IsoError::IsADirectory(_))
+        ));
+        assert!(matches!(
+            files.set_associated("APP/GAME", file::FileData::Directory(Vec::new())),
+            Err(IsoError::InvalidInput(_))
+        ));
+
+        let bytes = format_input(files, None, Vec::new()).unwrap();
+
+        let mut iso = IsoReader::new(Cursor::new(bytes.clone())).unwrap();
+        // The associated file is recorded first, but lookups find the file itself
+        let entries = iso.open_directory("/APP").unwrap().entries().unwrap();
+        let associated: Vec<bool> = entries
+            .iter()
+            .skip(2)
+            .map(|(_, entry)| entry.metadata().is_associated())
+            .collect();
+        assert_eq!(associated, [true, false]);
+        assert!(!iso.metadata("/APP/GAME").unwrap().is_associated());
+        assert_eq!(
+            iso.stat_associated("/APP/GAME").unwrap().metadata().len(),
+            10_000
+        );
+        assert!(matches!(
+            iso.stat_associated("/APP"),
+            Err(IsoError::NotFound(_))
+        ));
+
+        let mut read = Vec::new();
+        iso.open("/APP/GAME")
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, b"data fork");
+        read.clear();
+        iso.open_associated("/APP/GAME")
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, fork);
+
+        let slice = slice::IsoSlice::new(&bytes).unwrap();
+        assert_eq!(slice.read("/APP/GAME").unwrap(), b"data fork");
+    }
+
+    #[test]
+    fn test_volume_partition() {
+        let (_root, files) = stage_files(&[("README.TXT", b"hello")]);
+        let format = |partition| format_input(files.clone(), None, vec![partition]);
+        let vendor = VolumePartitionOptions {
+            identifier: "VENDOR_DATA".to_string(),
+            sectors: 4,
+            data: b"vendor".to_vec(),
+        };
+
+        assert!(matches!(
+            format(VolumePartitionOptions {
+                data: vec![1; 4 * 2048 + 1],
+                ..vendor.clone()
+            }),
+            Err(IsoError::InvalidInput(_))
+        ));
+        // The image only has 256 sectors
+        assert!(matches!(
+            format(VolumePartitionOptions {
+                sectors: 256,
+                ..vendor.clone()
+            }),
+            Err(IsoError::InvalidInput(_))
+        ));
+        let bytes = format(vendor).unwrap();
+
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+        let partition = iso.partition("VENDOR_DATA").unwrap();
+        assert_eq!(partition.volume_partition_size.read(), 4);
+        // The partition is placed after the file data
+        let location = partition.volume_partition_location.read();
+        assert!(location > iso.stat("/README.TXT").unwrap().header.extent.read());
+        let mut partition = iso.open_partition("VENDOR_DATA").unwrap();
+        assert_eq!(partition.len(), 4 * 2048);
+        let mut contents = Vec::new();
+        partition.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents.len(), 4 * 2048);
+        assert_eq!(&contents[..6], b"vendor");
+        assert!(contents[6..].iter().all(|&byte| byte == 0));
+        partition.seek(SeekFrom::Start(2)).unwrap();
+        let mut read = [0; 4];
+        partition.read_exact(&mut read).unwrap();
+        assert_eq!(&read, b"ndor");
+        assert!(matches!(
+            iso.open_partition("MISSING"),
+            Err(IsoError::NotFound(_))
+        ));
+        let mut readme = Vec::new();
+        iso.open("/README.TXT")
+            .unwrap()
+            .read_to_end(&mut readme)
+            .unwrap();
+        assert_eq!(readme, b"hello");
+    }
+}
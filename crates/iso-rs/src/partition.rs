@@ -0,0 +1,541 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::error::IsoError;
+
+/// The size of a logical block in MBR partition tables
+const MBR_BLOCK_SIZE: u64 = 512;
+/// The partition type of a protective MBR entry, which covers a GPT disk
+const GPT_PROTECTIVE_TYPE: u8 = 0xEE;
+/// The logical block sizes a GPT header is looked for with, in order
+const GPT_BLOCK_SIZES: [u64; 2] = [512, 4096];
+/// The number of GPT partition entries read at most, which is the minimum the entry array has
+/// room for and what partitioning tools create
+const GPT_MAX_ENTRIES: u32 = 128;
+
+/// The partition table a [`Partition`] was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKind {
+    /// A primary partition of an MBR, with its partition type
+    Mbr { partition_type: u8 },
+    /// A GPT partition, with its partition type GUID as stored on disk
+    Gpt { type_guid: [u8; 16] },
+}
+
+/// A partition of a disk image, see [`partitions`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Partition {
+    /// The index of the entry in the partition table, starting at 0
+    pub index: usize,
+    pub kind: PartitionKind,
+    /// The offset of the partition from the start of the disk in bytes
+    pub start: u64,
+    /// The size of the partition in bytes
+    pub size: u64,
+}
+
+/// Returns the partitions of a disk image.
+///
+/// This reads the MBR, and if it is a protective MBR, the GPT it covers instead. Disks without a
+/// partition table have no partitions.
+pub fn partitions<R: Read + Seek>(reader: &mut R) -> Result<Vec<Partition>, IsoError> {
+    let mut mbr = [0u8; 512];
+    reader.seek(SeekFrom::Start(0))?;
+    if !read_full(reader, &mut mbr)? || mbr[510..] != [0x55, 0xAA] {
+        return Ok(Vec::new());
+    }
+
+    let mut partitions = Vec::new();
+    for (index, entry) in mbr[446..510].chunks_exact(16).enumerate() {
+        let partition_type = entry[4];
+        let lba = u32::from_le_bytes(entry[8..12].try_into().unwrap()) as u64;
+        let sectors = u32::from_le_bytes(entry[12..16].try_into().unwrap()) as u64;
+        if partition_type == GPT_PROTECTIVE_TYPE
+            && let Some(gpt) = gpt_partitions(reader)?
+        {
+            return Ok(gpt);
+        }
+        if partition_type == 0 || sectors == 0 {
+            continue;
+        }
+        partitions.push(Partition {
+            index,
+            kind: PartitionKind::Mbr { partition_type },
+            start: lba * MBR_BLOCK_SIZE,
+            size: sectors * MBR_BLOCK_SIZE,
+        });
+    }
+    Ok(partitions)
+}
+
+/// An ISO 9660 filesystem found on a disk image, see [`find_iso_partitions`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IsoPartition {
+    /// The offset of the image from the start of the disk in bytes
+    pub offset: u64,
+    /// The bytes available to the image, up to the end of its partition, or of the disk if no
+    /// partition covers it
+    pub size: u64,
+    /// The partition the image is in, `None` for an image at the start of the disk which no
+    /// partition covers
+    pub partition: Option<Partition>,
+}
+
+/// Returns the ISO 9660 filesystems of a disk image, i.e. the offsets with a volume descriptor
+/// with the `CD001` identifier at LBA 16.
+///
+/// These are looked for at the start of each partition, and at the start of the disk. Hybrid
+/// images, like the ones written with [`crate::FormatOptions::protective_mbr`], start at the
+/// start of the disk, and have a partition that begins after the MBR and covers the rest of
+/// the image. Such a partition is returned with the image at offset 0.
+///
+/// The images can be opened with [`crate::IsoImage::new_at`], or through an [`OffsetReader`],
+/// with their offset and size.
+pub fn find_iso_partitions<R: Read + Seek>(reader: &mut R) -> Result<Vec<IsoPartition>, IsoError> {
+    const DESCRIPTORS_OFFSET: u64 = 16 * 2048;
+    let disk_size = reader.seek(SeekFrom::End(0))?;
+    let at_start = has_iso_at(reader, 0)?;
+    let mut found = Vec::new();
+    for partition in partitions(reader)? {
+        let covers_start = partition.start <= DESCRIPTORS_OFFSET
+            && partition.start.saturating_add(partition.size) > DESCRIPTORS_OFFSET;
+        let offset = if has_iso_at(reader, partition.start)? {
+            partition.start
+        } else if at_start && covers_start {
+            0
+        } else {
+            continue;
+        };
+        // Partition tables can claim more than the disk has
+        let end = partition
+            .start
+            .saturating_add(partition.size)
+            .min(disk_size);
+        found.push(IsoPartition {
+            offset,
+            size: end.saturating_sub(offset),
+            partition: Some(partition),
+        });
+    }
+    if at_start && !found.iter().any(|iso| iso.offset == 0) {
+        found.insert(
+            0,
+            IsoPartition {
+                offset: 0,
+                size: disk_size,
+                partition: None,
+            },
+        );
+    }
+    Ok(found)
+}
+
+/// Whether there is a volume descriptor at LBA 16 of an image starting at the given offset
+fn has_iso_at<R: Read + Seek>(reader: &mut R, offset: u64) -> Result<bool, IsoError> {
+    let Some(position) = offset.checked_add(16 * 2048) else {
+        return Ok(false);
+    };
+    let mut header = [0u8; 6];
+    reader.seek(SeekFrom::Start(position))?;
+    Ok(read_full(reader, &mut header)? && &header[1..] == b"CD001")
+}
+
+/// Reads the GPT entries, returning `None` if there is no valid GPT header
+fn gpt_partitions<R: Read + Seek>(reader: &mut R) -> Result<Option<Vec<Partition>>, IsoError> {
+    // The header is at LBA 1, but the size of a logical block depends on the disk
+    let mut header = [0u8; 92];
+    let mut block_size = None;
+    for size in GPT_BLOCK_SIZES {
+        reader.seek(SeekFrom::Start(size))?;
+        if read_full(reader, &mut header)? && &header[..8] == b"EFI PART" {
+            block_size = Some(size);
+            break;
+        }
+    }
+    let Some(block_size) = block_size else {
+        return Ok(None);
+    };
+
+    let invalid = IsoError::InvalidData;
+    let entries_lba = u64::from_le_bytes(header[72..80].try_into().unwrap());
+    let entry_count = u32::from_le_bytes(header[80..84].try_into().unwrap());
+    let entry_size = u32::from_le_bytes(header[84..88].try_into().unwrap()) as u64;
+    if entry_size < 128 || !entry_size.is_multiple_of(128) || entry_size > block_size {
+        return Err(invalid("GPT partition entries have an invalid size"));
+    }
+    if entry_count > GPT_MAX_ENTRIES {
+        return Err(invalid("GPT has too many partition entries"));
+    }
+    let entries_offset = entries_lba
+        .checked_mul(block_size)
+        .ok_or(invalid("GPT partition entries are out of range"))?;
+
+    let mut partitions = Vec::new();
+    let mut entry = vec![0u8; entry_size as usize];
+    reader.seek(SeekFrom::Start(entries_offset))?;
+    for index in 0..entry_count as usize {
+        reader.read_exact(&mut entry)?;
+        let type_guid: [u8; 16] = entry[..16].try_into().unwrap();
+        if type_guid == [0; 16] {
+            // Unused entry
+            continue;
+        }
+        let first_lba = u64::from_le_bytes(entry[32..40].try_into().unwrap());
+        // The last LBA is inclusive
+        let last_lba = u64::from_le_bytes(entry[40..48].try_into().unwrap());
+        let out_of_range = || invalid("GPT partition is out of range");
+        let start = first_lba.checked_mul(block_size).ok_or_else(out_of_range)?;
+        let size = last_lba
+            .checked_add(1)
+            .and_then(|end| end.saturating_sub(first_lba).checked_mul(block_size))
+            .ok_or_else(out_of_range)?;
+        partitions.push(Partition {
+            index,
+            kind: PartitionKind::Gpt { type_guid },
+            start,
+            size,
+        });
+    }
+    Ok(Some(partitions))
+}
+
+/// Fills the buffer, returning `false` if the reader ended first
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool, std::io::Error> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// A view of the bytes of a device between an offset and a length, e.g. a [`Partition`].
+///
+/// Positions are relative to the offset, and reads and writes end at the length, so an image
+/// inside a partition can be opened with [`crate::reader::IsoReader`] just like a standalone
+/// image:
+/// ```no_run
+/// # use iso9660_rs::{partition::{OffsetReader, find_iso_partitions}, reader::IsoReader};
+/// let mut disk = std::fs::File::open("disk.img").unwrap();
+/// let found = find_iso_partitions(&mut disk).unwrap()[0];
+/// let mut iso = IsoReader::new(OffsetReader::new(disk, found.offset, found.size)).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct OffsetReader<T> {
+    inner: T,
+    offset: u64,
+    len: u64,
+    /// The position in the view, which is read from the device until the first seek
+    position: Option<u64>,
+}
+
+impl<T> OffsetReader<T> {
+    /// A view of the `len` bytes of the device starting at `offset`
+    pub fn new(inner: T, offset: u64, len: u64) -> Self {
+        Self {
+            inner,
+            offset,
+            len,
+            position: None,
+        }
+    }
+
+    /// The offset of the view into the underlying device in bytes
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The size of the view in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Seek> OffsetReader<T> {
+    fn position(&mut self) -> std::io::Result<u64> {
+        if let Some(position) = self.position {
+            return Ok(position);
+        }
+        let position = self
+            .inner
+            .stream_position()?
+            .checked_sub(self.offset)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "the device is positioned before the start of the offset",
+                )
+            })?;
+        self.position = Some(position);
+        Ok(position)
+    }
+
+    /// The number of bytes up to `max` that can be accessed before the end of the view
+    fn remaining(&mut self, max: usize) -> std::io::Result<usize> {
+        Ok(self.len.saturating_sub(self.position()?).min(max as u64) as usize)
+    }
+}
+
+impl<T: Read + Seek> Read for OffsetReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.remaining(buf.len())?;
+        let read = self.inner.read(&mut buf[..len])?;
+        self.position = self.position.map(|position| position + read as u64);
+        Ok(read)
+    }
+}
+
+impl<T: Seek> Seek for OffsetReader<T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position()?.checked_add_signed(offset),
+        };
+        let Some((position, absolute)) =
+            position.and_then(|position| Some((position, self.offset.checked_add(position)?)))
+        else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ));
+        };
+        self.inner.seek(SeekFrom::Start(absolute))?;
+        self.position = Some(position);
+        Ok(position)
+    }
+}
+
+impl<T: Write + Seek> Write for OffsetReader<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.remaining(buf.len())?;
+        let written = self.inner.write(&buf[..len])?;
+        self.position = self.position.map(|position| position + written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        FormatOptions, IsoImage,
+        directory::MAX_EXTENT_SIZE,
+        tests::{format_image, format_options, options, stage_files},
+    };
+
+    /// The offset of the image in the test disks, 1 MiB like most partitioning tools use
+    const START: u64 = 1024 * 1024;
+
+    fn disk_with(iso: &[u8], table: impl FnOnce(&mut [u8])) -> Vec<u8> {
+        let mut disk = vec![0u8; START as usize];
+        table(&mut disk);
+        disk.extend_from_slice(iso);
+        disk
+    }
+
+    fn mbr_entry(disk: &mut [u8], index: usize, partition_type: u8, lba: u32, sectors: u32) {
+        let entry = &mut disk[446 + index * 16..][..16];
+        entry[4] = partition_type;
+        entry[8..12].copy_from_slice(&lba.to_le_bytes());
+        entry[12..16].copy_from_slice(&sectors.to_le_bytes());
+        disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+    }
+
+    #[test]
+    fn test_mbr_partitions() {
+        let iso = format_image(&[("BOOT/KERNEL", b"kernel")], None);
+        let mut disk = disk_with(&iso, |disk| {
+            // A partition without a filesystem, before the image
+            mbr_entry(disk, 0, 0x83, 64, 64);
+            mbr_entry(disk, 1, 0x96, (START / 512) as u32, iso.len() as u32 / 512);
+        });
+        // Data after the partition isn't part of the image
+        disk.extend_from_slice(&[0xFF; 4096]);
+        let mut disk = Cursor::new(disk);
+
+        assert_eq!(partitions(&mut disk).unwrap().len(), 2);
+        let found = find_iso_partitions(&mut disk).unwrap();
+        assert_eq!(
+            found,
+            vec![IsoPartition {
+                offset: START,
+                size: iso.len() as u64,
+                partition: Some(Partition {
+                    index: 1,
+                    kind: PartitionKind::Mbr {
+                        partition_type: 0x96
+                    },
+                    start: START,
+                    size: iso.len() as u64,
+                }),
+            }]
+        );
+
+        let mut iso_image = IsoImage::new_at(&mut disk, found[0].offset, found[0].size).unwrap();
+        assert_eq!(iso_image.size(), iso.len() as u64);
+        let mut file = Vec::new();
+        iso_image
+            .open("/BOOT/KERNEL")
+            .unwrap()
+            .read_to_end(&mut file)
+            .unwrap();
+        assert_eq!(file, b"kernel");
+    }
+
+    #[test]
+    fn test_gpt_partitions() {
+        let iso = format_image(&[("README.TXT", b"hello")], None);
+        let type_guid = [0xAB; 16];
+        let mut disk = Cursor::new(disk_with(&iso, |disk| {
+            mbr_entry(disk, 0, GPT_PROTECTIVE_TYPE, 1, u32::MAX);
+            let header = &mut disk[512..1024];
+            header[..8].copy_from_slice(b"EFI PART");
+            header[72..80].copy_from_slice(&2u64.to_le_bytes());
+            header[80..84].copy_from_slice(&4u32.to_le_bytes());
+            header[84..88].copy_from_slice(&128u32.to_le_bytes());
+            // The second entry is used, the rest are empty
+            let entry = &mut disk[1024 + 128..][..128];
+            entry[..16].copy_from_slice(&type_guid);
+            entry[32..40].copy_from_slice(&(START / 512).to_le_bytes());
+            let last_lba = (START + iso.len() as u64) / 512 - 1;
+            entry[40..48].copy_from_slice(&last_lba.to_le_bytes());
+        }));
+
+        let found = find_iso_partitions(&mut disk).unwrap();
+        assert_eq!(
+            found,
+            vec![IsoPartition {
+                offset: START,
+                size: iso.len() as u64,
+                partition: Some(Partition {
+                    index: 1,
+                    kind: PartitionKind::Gpt { type_guid },
+                    start: START,
+                    size: iso.len() as u64,
+                }),
+            }]
+        );
+
+        // The disk has room for another partition after the image
+        disk.get_mut().extend_from_slice(&[0xFF; 4096]);
+        let view = OffsetReader::new(disk, found[0].offset, found[0].size);
+        let mut iso_image = crate::reader::IsoReader::new(view).unwrap();
+        assert_eq!(iso_image.size(), iso.len() as u64);
+        assert!(iso_image.metadata("/README.TXT").unwrap().is_file());
+        let mut reader = iso_image.into_inner();
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        assert!(reader.seek(SeekFrom::Current(-1)).is_err());
+
+        // Reads end at the end of the view
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, iso[iso.len() - 2..]);
+    }
+
+    #[test]
+    fn test_invalid_gpt() {
+        let gpt = |entry_count: u32, entry_size: u32, first_lba: u64, last_lba: u64| {
+            let mut disk = Cursor::new(disk_with(&[], |disk| {
+                mbr_entry(disk, 0, GPT_PROTECTIVE_TYPE, 1, u32::MAX);
+                let header = &mut disk[512..1024];
+                header[..8].copy_from_slice(b"EFI PART");
+                header[72..80].copy_from_slice(&2u64.to_le_bytes());
+                header[80..84].copy_from_slice(&entry_count.to_le_bytes());
+                header[84..88].copy_from_slice(&entry_size.to_le_bytes());
+                let entry = &mut disk[1024..][..128];
+                entry[..16].copy_from_slice(&[0xAB; 16]);
+                entry[32..40].copy_from_slice(&first_lba.to_le_bytes());
+                entry[40..48].copy_from_slice(&last_lba.to_le_bytes());
+            }));
+            partitions(&mut disk)
+        };
+
+        assert_eq!(gpt(4, 128, 2048, 4095).unwrap().len(), 1);
+        for (entry_count, entry_size) in [(4, 100), (4, 200), (4, 1024), (u32::MAX, 128)] {
+            assert!(matches!(
+                gpt(entry_count, entry_size, 2048, 4095),
+                Err(IsoError::InvalidData(_))
+            ));
+        }
+        assert!(matches!(
+            gpt(4, 128, 2048, u64::MAX),
+            Err(IsoError::InvalidData(_))
+        ));
+        assert!(matches!(
+            gpt(4, 128, u64::MAX / 2, u64::MAX / 2),
+            Err(IsoError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_protective_mbr() {
+        let (_root, files) = stage_files(&[("README.TXT", b"hello")]);
+        let options = FormatOptions {
+            protective_mbr: true,
+            ..options(files)
+        };
+        let mut disk = Cursor::new(format_options(options, MAX_EXTENT_SIZE).unwrap());
+
+        // The partition starts after the MBR, inside the system area of the image
+        let found = find_iso_partitions(&mut disk).unwrap();
+        assert_eq!(
+            found,
+            vec![IsoPartition {
+                offset: 0,
+                size: 256 * 2048,
+                partition: Some(Partition {
+                    index: 0,
+                    kind: PartitionKind::Mbr {
+                        partition_type: 0x17
+                    },
+                    start: 512,
+                    size: 256 * 2048 - 512,
+                }),
+            }]
+        );
+        let mut iso_image = IsoImage::new_at(&mut disk, found[0].offset, found[0].size).unwrap();
+        assert!(iso_image.metadata("/README.TXT").unwrap().is_file());
+    }
+
+    #[test]
+    fn test_no_partition_table() {
+        let iso = format_image(&[("README.TXT", b"hello")], None);
+        assert!(partitions(&mut Cursor::new(&iso)).unwrap().is_empty());
+        // The image at the start of the disk is still found
+        assert_eq!(
+            find_iso_partitions(&mut Cursor::new(&iso)).unwrap(),
+            vec![IsoPartition {
+                offset: 0,
+                size: iso.len() as u64,
+                partition: None,
+            }]
+        );
+        assert!(
+            partitions(&mut Cursor::new(&[0u8; 100]))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
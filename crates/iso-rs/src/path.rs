@@ -1,13 +1,18 @@
+#[cfg(feature = "std")]
+use alloc::vec;
+use alloc::{string::String, vec::Vec};
+#[cfg(feature = "std")]
 use std::io::Read;
 
-use crate::types::EndianType;
+use crate::{error::IsoError, types::EndianType};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct PathTableEntryHeader {
     pub len: u8,
     pub extended_attr_record: u8,
-    pub parent_lba: [u8; 4],
+    /// The LBA of the directory
+    pub extent: [u8; 4],
     pub parent_directory_number: [u8; 2],
 }
 
@@ -15,19 +20,41 @@ impl PathTableEntryHeader {
     pub fn from_bytes(bytes: &[u8]) -> Self {
         *bytemuck::from_bytes(bytes)
     }
+
+    /// Splits the path table entry at the start of the given bytes into its header and its
+    /// directory identifier, without copying either
+    pub fn split(bytes: &[u8]) -> Result<(&Self, &[u8]), IsoError> {
+        const HEADER_SIZE: usize = size_of::<PathTableEntryHeader>();
+        let header: &Self =
+            bytes
+                .get(..HEADER_SIZE)
+                .map(bytemuck::from_bytes)
+                .ok_or(IsoError::InvalidData(
+                    "path table entry header is truncated",
+                ))?;
+        let name = bytes
+            .get(HEADER_SIZE..HEADER_SIZE + header.len as usize)
+            .ok_or(IsoError::InvalidData(
+                "path table entry identifier is truncated",
+            ))?;
+        Ok((header, name))
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PathTableEntry {
     pub length: u8,
     pub extended_attr_record: u8,
-    pub parent_lba: u32,
+    /// The LBA of the directory
+    pub extent: u32,
+    /// The 1-based index of the parent directory in the path table
     pub parent_index: u16,
     pub name: String,
 }
 
 impl PathTableEntry {
-    pub fn parse<T: Read>(reader: &mut T, endian: EndianType) -> Result<Self, std::io::Error> {
+    #[cfg(feature = "std")]
+    pub fn parse<T: Read>(reader: &mut T, endian: EndianType) -> Result<Self, IsoError> {
         let mut buf = [0; size_of::<PathTableEntryHeader>()];
         reader.read_exact(&mut buf)?;
         let header = PathTableEntryHeader::from_bytes(&buf);
@@ -41,31 +68,195 @@ impl PathTableEntry {
         Ok(Self {
             length: header.len,
             extended_attr_record: header.extended_attr_record,
-            parent_lba: endian.read_u32(header.parent_lba),
+            extent: endian.read_u32(header.extent),
             parent_index: endian.read_u16(header.parent_directory_number),
-            name: String::from_utf8(name).unwrap(),
+            name: String::from_utf8(name).map_err(|err| {
+                IsoError::InvalidName(String::from_utf8_lossy(err.as_bytes()).into())
+            })?,
         })
     }
 
-    pub fn to_bytes(&self, endian: EndianType) -> Vec<u8> {
+    pub fn to_bytes(&self, endian: EndianType) -> Result<Vec<u8>, IsoError> {
+        if self.name.len() > u8::MAX as usize {
+            return Err(IsoError::InvalidName(self.name.clone()));
+        }
         let mut bytes = Vec::new();
         let header = PathTableEntryHeader {
             len: self.name.len() as u8,
             extended_attr_record: 0,
-            parent_lba: endian.u32_bytes(self.parent_lba),
+            extent: endian.u32_bytes(self.extent),
             parent_directory_number: endian.u16_bytes(self.parent_index),
         };
         bytes.extend_from_slice(bytemuck::bytes_of(&header));
         bytes.extend_from_slice(self.name.as_bytes());
-        assert_eq!(header.len as usize, self.name.len());
         if header.len % 2 == 1 {
             bytes.push(0);
         }
 
-        bytes
+        Ok(bytes)
     }
+
     pub fn size(&self) -> usize {
-        let size = (size_of::<PathTableEntryHeader>() + self.name.len() + 1) & !1;
-        size
+        (size_of::<PathTableEntryHeader>() + self.name.len() + 1) & !1
+    }
+}
+
+/// One of the copies of the path table recorded in the primary volume descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathTableKind {
+    /// The little endian path table
+    TypeL,
+    /// The big endian path table
+    TypeM,
+    /// The optional copy of the little endian path table
+    OptionalTypeL,
+    /// The optional copy of the big endian path table
+    OptionalTypeM,
+}
+
+impl PathTableKind {
+    pub const ALL: [PathTableKind; 4] = [
+        PathTableKind::TypeL,
+        PathTableKind::TypeM,
+        PathTableKind::OptionalTypeL,
+        PathTableKind::OptionalTypeM,
+    ];
+
+    /// The byte order of the numbers in the table
+    pub fn endian(self) -> EndianType {
+        match self {
+            PathTableKind::TypeL | PathTableKind::OptionalTypeL => EndianType::LittleEndian,
+            PathTableKind::TypeM | PathTableKind::OptionalTypeM => EndianType::BigEndian,
+        }
+    }
+}
+
+impl core::fmt::Display for PathTableKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            PathTableKind::TypeL => "type L",
+            PathTableKind::TypeM => "type M",
+            PathTableKind::OptionalTypeL => "optional type L",
+            PathTableKind::OptionalTypeM => "optional type M",
+        })
+    }
+}
+
+/// A directory from the path table, see [`DirectoryTree`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTableDirectory {
+    /// The full path of the directory without a leading slash, which is empty for the root
+    pub path: String,
+    /// The index of the parent directory in [`DirectoryTree::directories`], the root directory
+    /// is its own parent
+    pub parent: usize,
+    /// The LBA of the directory
+    pub extent: u32,
+}
+
+impl PathTableDirectory {
+    /// The last component of the path, which is empty for the root
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or_default()
     }
 }
+
+/// The directory hierarchy described by the path table, with every parent resolved.
+///
+/// Directories are in path table order, so every directory comes after its parent, and the root
+/// directory is always first. Listing directories this way only reads the path table, instead
+/// of every directory extent.
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryTree {
+    directories: Vec<PathTableDirectory>,
+}
+
+impl DirectoryTree {
+    /// Resolves the parents of the given path table entries
+    pub fn from_entries(entries: &[PathTableEntry]) -> Result<Self, IsoError> {
+        let mut directories: Vec<PathTableDirectory> = Vec::with_capacity(entries.len());
+        for (index, entry) in entries.iter().enumerate() {
+            if index == 0 {
+                directories.push(PathTableDirectory {
+                    path: String::new(),
+                    parent: 0,
+                    extent: entry.extent,
+                });
+                continue;
+            }
+            // Parents always come first, so a parent index that doesn't would make a cycle
+            let parent = (entry.parent_index as usize)
+                .checked_sub(1)
+                .filter(|&parent| parent < index)
+                .ok_or(IsoError::InvalidData(
+                    "path table entry has an invalid parent directory number",
+                ))?;
+            let parent_path = &directories[parent].path;
+            let path = if parent_path.is_empty() {
+                entry.name.clone()
+            } else {
+                alloc::format!("{}/{}", parent_path, entry.name)
+            };
+            directories.push(PathTableDirectory {
+                path,
+                parent,
+                extent: entry.extent,
+            });
+        }
+        Ok(Self { directories })
+    }
+
+    /// Every directory, in path table order
+    pub fn directories(&self) -> &[PathTableDirectory] {
+        &self.directories
+    }
+
+    pub fn root(&self) -> Option<&PathTableDirectory> {
+        self.directories.first()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&PathTableDirectory> {
+        self.directories.get(index)
+    }
+
+    /// Returns the parent of the directory at the given index, or `None` for the root
+    pub fn parent(&self, index: usize) -> Option<&PathTableDirectory> {
+        self.directories
+            .get(index)
+            .filter(|_| index != 0)
+            .map(|directory| &self.directories[directory.parent])
+    }
+
+    /// Returns the subdirectories of the directory at the given index, along with their indices
+    pub fn children(&self, index: usize) -> impl Iterator<Item = (usize, &PathTableDirectory)> {
+        self.directories
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(move |(_, directory)| directory.parent == index)
+    }
+
+    /// Finds the index of the directory at the given path
+    pub fn find(&self, path: &str) -> Option<usize> {
+        let path = path.trim_matches('/');
+        self.directories
+            .iter()
+            .position(|directory| directory.path == path)
+    }
+}
+
+/// A difference between the path table and the directory hierarchy, see
+/// [`crate::reader::IsoReader::check_path_table`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathTableMismatch {
+    /// The directory is in the path table, but can't be reached from the root directory
+    MissingFromHierarchy { path: String, extent: u32 },
+    /// The directory can be reached from the root directory, but isn't in the path table
+    MissingFromPathTable { path: String, extent: u32 },
+    /// The directory is in both, but at different extents
+    ExtentMismatch {
+        path: String,
+        path_table: u32,
+        hierarchy: u32,
+    },
+}
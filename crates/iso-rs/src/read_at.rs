@@ -0,0 +1,121 @@
+use std::{
+    io::{Read, Seek, SeekFrom},
+    sync::Arc,
+};
+
+/// A source that can be read at arbitrary offsets through a shared reference.
+///
+/// Unlike [`Read`] and [`Seek`], reads don't move a shared cursor, so many readers can use the
+/// same source at once, see [`crate::reader::SharedIsoReader`].
+pub trait ReadAt {
+    /// Reads bytes starting at the given offset, returning the number of bytes read, which is
+    /// 0 at the end of the source
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, std::io::Error>;
+
+    /// The size of the source in bytes
+    fn size(&self) -> Result<u64, std::io::Error>;
+}
+
+impl ReadAt for [u8] {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, std::io::Error> {
+        let start = offset.min(self.len() as u64) as usize;
+        let len = buf.len().min(self.len() - start);
+        buf[..len].copy_from_slice(&self[start..start + len]);
+        Ok(len)
+    }
+
+    fn size(&self) -> Result<u64, std::io::Error> {
+        Ok(self.len() as u64)
+    }
+}
+
+impl ReadAt for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, std::io::Error> {
+        self.as_slice().read_at(buf, offset)
+    }
+
+    fn size(&self) -> Result<u64, std::io::Error> {
+        Ok(self.len() as u64)
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl ReadAt for std::fs::File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, std::io::Error> {
+        #[cfg(unix)]
+        return std::os::unix::fs::FileExt::read_at(self, buf, offset);
+        // This moves the file cursor, but every read passes its own offset, so that is harmless
+        #[cfg(windows)]
+        return std::os::windows::fs::FileExt::seek_read(self, buf, offset);
+    }
+
+    fn size(&self) -> Result<u64, std::io::Error> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for &T {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, std::io::Error> {
+        (**self).read_at(buf, offset)
+    }
+
+    fn size(&self) -> Result<u64, std::io::Error> {
+        (**self).size()
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for Arc<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, std::io::Error> {
+        (**self).read_at(buf, offset)
+    }
+
+    fn size(&self) -> Result<u64, std::io::Error> {
+        (**self).size()
+    }
+}
+
+/// Adapts a [`ReadAt`] source to [`Read`] and [`Seek`], with a cursor of its own
+#[derive(Debug, Clone)]
+pub struct ReadAtCursor<R: ReadAt> {
+    inner: R,
+    position: u64,
+}
+
+impl<R: ReadAt> ReadAtCursor<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, position: 0 }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: ReadAt> Read for ReadAtCursor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read_at(buf, self.position)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: ReadAt> Seek for ReadAtCursor<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.inner.size()?.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
@@ -0,0 +1,1055 @@
+use std::{
+    collections::BTreeMap,
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::{
+    IsoDirectory, IsoFile, IsoPathTable, PathTableRef,
+    attribute::ExtendedAttributeRecord,
+    boot::BootCatalogue,
+    directory::{
+        DirectoryRecord, DirectoryRecordHeader, DirectoryRef, FileFlags, MAX_EXTENT_SIZE, Metadata,
+    },
+    error::IsoError,
+    path::PathTableMismatch,
+    read_at::{ReadAt, ReadAtCursor},
+    recover::{self, ParseWarning, Recovery, Warnings},
+    session::{self, Session},
+    split_path,
+    types::{Endian, IsoStringFile},
+    volume::{VolumeDescriptorList, VolumePartitionDescriptor},
+    walk::Walk,
+};
+
+/// A read-only ISO image.
+///
+/// Unlike [`crate::IsoImage`], this only requires [`Read`] and [`Seek`], so it can be used with
+/// read-only files, or in-memory images through [`std::io::Cursor`]:
+/// ```no_run
+/// # use std::io::Cursor;
+/// # use iso9660_rs::reader::IsoReader;
+/// static IMAGE: &[u8] = &[]; // include_bytes!("image.iso")
+/// let mut iso = IsoReader::new(Cursor::new(IMAGE)).unwrap();
+/// let entries = iso.root_directory().entries().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct IsoReader<R: Read + Seek> {
+    reader: R,
+    size: u64,
+
+    volume_descriptors: VolumeDescriptorList,
+    boot_catalogue: Option<BootCatalogue>,
+    /// The header of the root directory record
+    root: DirectoryRecordHeader,
+    root_directory: DirectoryRef,
+    path_table: PathTableRef,
+    /// Whether lookups fall back to reading every directory along the path, see
+    /// [`IsoReader::new_lenient`]
+    lenient: bool,
+    /// Whether lookups ignore ASCII case, see [`IsoReader::case_insensitive`]
+    case_insensitive: bool,
+}
+
+impl<R: Read + Seek> IsoReader<R> {
+    /// Opens the image through the volume descriptor set at LBA 16.
+    ///
+    /// For a multi-session image this is the first session, unless the writer copied the
+    /// descriptors of the latest one there. Later sessions are found with
+    /// [`session::sessions`], and opened with [`IsoReader::new_session`].
+    pub fn new(reader: R) -> Result<Self, IsoError> {
+        Self::new_session(reader, 0)
+    }
+
+    /// Opens the session of a multi-session image that starts at the given LBA, e.g. to
+    /// inspect the files of an older session
+    pub fn new_session(mut reader: R, start: u64) -> Result<Self, IsoError> {
+        let volume_descriptors = Session::read(&mut reader, start)?.volume_descriptors;
+        let size = reader.seek(SeekFrom::End(0))?;
+
+        let boot_catalogue = match volume_descriptors.boot_record() {
+            Some(boot) => {
+                reader.seek(SeekFrom::Start(boot.catalog_ptr.get() as u64 * 2048))?;
+                Some(BootCatalogue::parse(&mut reader)?)
+            }
+            None => None,
+        };
+
+        let pvd = volume_descriptors
+            .primary()
+            .ok_or(IsoError::MissingPrimaryDescriptor)?;
+        let root = pvd.dir_record.header;
+        let path_table = PathTableRef::new(pvd);
+
+        Ok(Self::from_parts(
+            reader,
+            size,
+            volume_descriptors,
+            boot_catalogue,
+            root,
+            path_table,
+        ))
+    }
+
+    /// Opens a damaged image, recovering as much of it as possible instead of failing.
+    ///
+    /// Invalid volume descriptors and a missing terminator are skipped, and a boot catalogue
+    /// with a bad checksum is still read. If the root directory record is damaged, the root
+    /// directory is found through the path table, or by scanning every sector of the image.
+    /// Lookups that fail through the path table fall back to reading every directory along the
+    /// path, see [`IsoReader::recover`] to list everything that can still be found.
+    ///
+    /// Every problem that was skipped is returned as a warning, this only fails if the root
+    /// directory can't be found at all.
+    pub fn new_lenient(mut reader: R) -> Result<(Self, Vec<ParseWarning>), IsoError> {
+        let mut warnings = Warnings::default();
+        let size = reader.seek(SeekFrom::End(0))?;
+        let volume_descriptors = recover::parse_descriptors(&mut reader, &mut warnings);
+
+        let boot_catalogue = volume_descriptors.boot_record().and_then(|boot| {
+            recover::parse_boot_catalogue(&mut reader, boot.catalog_ptr.get() as u64, &mut warnings)
+        });
+
+        let pvd = volume_descriptors.primary();
+        let root = recover::find_root(&mut reader, size, pvd, &mut warnings)?;
+        // Without a primary volume descriptor there is no path table, so lookups always read
+        // the directories
+        let path_table = pvd.map_or(PathTableRef::default(), PathTableRef::new);
+
+        let mut iso = Self::from_parts(
+            reader,
+            size,
+            volume_descriptors,
+            boot_catalogue,
+            root,
+            path_table,
+        );
+        iso.lenient = true;
+        Ok((iso, warnings.0))
+    }
+
+    fn from_parts(
+        reader: R,
+        size: u64,
+        volume_descriptors: VolumeDescriptorList,
+        boot_catalogue: Option<BootCatalogue>,
+        root: DirectoryRecordHeader,
+        path_table: PathTableRef,
+    ) -> Self {
+        Self {
+            reader,
+            size,
+
+            volume_descriptors,
+            boot_catalogue,
+            root,
+            root_directory: DirectoryRef {
+                offset: root.extent.read() as u64,
+                size: root.data_len.read() as u64,
+            },
+            path_table,
+            lenient: false,
+            case_insensitive: false,
+        }
+    }
+
+    /// Makes lookups by path ignore ASCII case, so `/boot/grub/grub.cfg` finds
+    /// `/BOOT/GRUB/GRUB.CFG;1`
+    pub fn case_insensitive(mut self, enabled: bool) -> Self {
+        self.case_insensitive = enabled;
+        self
+    }
+
+    /// The size of the underlying image in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn volume_descriptors(&self) -> &VolumeDescriptorList {
+        &self.volume_descriptors
+    }
+
+    /// The El Torito boot catalogue, if the image has a boot record
+    pub fn boot_catalogue(&self) -> Option<&BootCatalogue> {
+        self.boot_catalogue.as_ref()
+    }
+
+    /// The volume partition descriptor with the given identifier
+    pub fn partition(&self, identifier: &str) -> Option<&VolumePartitionDescriptor> {
+        self.volume_descriptors
+            .partitions()
+            .find(|partition| partition.volume_partition_identifier.to_str() == identifier)
+    }
+
+    /// Opens the contents of the volume partition with the given identifier for reading, which
+    /// are bounded by the extent of the partition
+    pub fn open_partition(&mut self, identifier: &str) -> Result<IsoFile<&mut R>, IsoError> {
+        let extent = self
+            .partition(identifier)
+            .ok_or_else(|| IsoError::NotFound(identifier.to_string()))?
+            .extent();
+        if extent.offset * 2048 + extent.size > self.size {
+            return Err(IsoError::ExtentOutOfRange(extent));
+        }
+        // Partitions can be larger than a single extent of a file
+        let mut records = DirectoryRecord::new_multi_extent(
+            identifier.as_bytes(),
+            extent,
+            FileFlags::empty(),
+            MAX_EXTENT_SIZE,
+        )?
+        .into_iter();
+        let mut record = records.next().unwrap();
+        record.continuation = records.map(|record| record.extent()).collect();
+        Ok(self.open_record(record))
+    }
+
+    pub fn root_directory(&mut self) -> IsoDirectory<'_, R> {
+        IsoDirectory {
+            reader: &mut self.reader,
+            directory: self.root_directory,
+        }
+    }
+
+    pub fn path_table(&mut self) -> IsoPathTable<'_, R> {
+        IsoPathTable {
+            reader: &mut self.reader,
+            path_table: self.path_table,
+        }
+    }
+
+    /// Returns the directory record of the entry at the given path.
+    ///
+    /// Paths are relative to the root directory, and components are separated by `/`,
+    /// e.g. `/EFI/BOOT/BOOTX64.EFI`.
+    pub fn stat(&mut self, path: &str) -> Result<DirectoryRecord, IsoError> {
+        self.lookup().stat(&mut self.reader, path, false)
+    }
+
+    /// Returns the metadata of the entry at the given path, see [`IsoReader::stat`]
+    pub fn metadata(&mut self, path: &str) -> Result<Metadata, IsoError> {
+        Ok(self.stat(path)?.metadata())
+    }
+
+    /// Returns the directory record of the associated file of the entry at the given path, such
+    /// as the resource fork of a file on a Macintosh CD-ROM.
+    ///
+    /// The associated file has the same identifier as its entry, which is what
+    /// [`IsoReader::stat`] returns.
+    pub fn stat_associated(&mut self, path: &str) -> Result<DirectoryRecord, IsoError> {
+        self.lookup().stat(&mut self.reader, path, true)
+    }
+
+    /// Returns the extended attribute record of the entry at the given path, `None` if it doesn't
+    /// have one
+    pub fn extended_attributes(
+        &mut self,
+        path: &str,
+    ) -> Result<Option<ExtendedAttributeRecord>, IsoError> {
+        let record = self.stat(path)?;
+        self.extended_attributes_of(&record)
+    }
+
+    /// Returns the extended attribute record of the entry described by the given record, e.g.
+    /// one yielded by [`Walk`]
+    pub fn extended_attributes_of(
+        &mut self,
+        record: &DirectoryRecord,
+    ) -> Result<Option<ExtendedAttributeRecord>, IsoError> {
+        ExtendedAttributeRecord::read(&mut self.reader, record)
+    }
+
+    /// Opens the file at the given path for reading
+    pub fn open(&mut self, path: &str) -> Result<IsoFile<&mut R>, IsoError> {
+        let record = self.stat(path)?;
+        if record.header.is_directory() {
+            return Err(IsoError::IsADirectory(path.to_string()));
+        }
+        Ok(self.open_record(record))
+    }
+
+    /// Opens the associated file of the entry at the given path for reading, see
+    /// [`IsoReader::stat_associated`]
+    pub fn open_associated(&mut self, path: &str) -> Result<IsoFile<&mut R>, IsoError> {
+        let record = self.stat_associated(path)?;
+        Ok(self.open_record(record))
+    }
+
+    /// Opens the file described by the given record, e.g. one yielded by [`Walk`]
+    pub fn open_record(&mut self, record: DirectoryRecord) -> IsoFile<&mut R> {
+        IsoFile::new(&mut self.reader, record)
+    }
+
+    /// Opens the directory at the given path
+    pub fn open_directory(&mut self, path: &str) -> Result<IsoDirectory<'_, R>, IsoError> {
+        let lookup = self.lookup();
+        let found = self
+            .path_table()
+            .find_directory_matching(path, lookup.case_insensitive);
+        let directory = match found {
+            Ok(Some(directory)) => directory,
+            // The directory may still be found through its parent, see `PathLookup::stat`
+            Ok(None) | Err(_) if lookup.lenient => {
+                let record = lookup.stat(&mut self.reader, path, false)?;
+                if !record.header.is_directory() {
+                    return Err(not_found(path));
+                }
+                record.extent()
+            }
+            found => found?.ok_or_else(|| not_found(path))?,
+        };
+        Ok(IsoDirectory {
+            reader: &mut self.reader,
+            directory,
+        })
+    }
+
+    /// Recursively walks the whole directory tree, see [`Walk`] for the available options
+    pub fn walk(&mut self) -> Walk<'_, R> {
+        self.root_directory().walk()
+    }
+
+    /// Lists every entry that can still be found in a damaged image, along with the problems
+    /// that were skipped.
+    ///
+    /// Unlike [`IsoReader::walk`], this doesn't stop at the first error. The rest of a sector is
+    /// skipped after an invalid record, records with extents past the end of the image are
+    /// left out, and directories that can't be reached through their parent are found through
+    /// the path table.
+    pub fn recover(&mut self) -> Recovery {
+        recover::recover(&mut self.reader, &self.root, self.path_table, self.size)
+    }
+
+    /// Compares the path table with the directory hierarchy, returning every directory that is
+    /// only in one of them or has a different extent in each.
+    ///
+    /// Readers use the path table to look up directories, so an image where the two disagree
+    /// can show different contents depending on how it's read.
+    pub fn check_path_table(&mut self) -> Result<Vec<PathTableMismatch>, IsoError> {
+        let mut hierarchy = BTreeMap::new();
+        for entry in self.walk() {
+            let entry = entry?;
+            if entry.is_directory() {
+                hierarchy.insert(entry.path, entry.record.header.extent.read());
+            }
+        }
+        hierarchy.insert(String::new(), self.root.extent.read());
+
+        let mut mismatches = Vec::new();
+        for directory in self.path_table().tree()?.directories() {
+            match hierarchy.remove(&directory.path) {
+                None => mismatches.push(PathTableMismatch::MissingFromHierarchy {
+                    path: directory.path.clone(),
+                    extent: directory.extent,
+                }),
+                Some(extent) if extent != directory.extent => {
+                    mismatches.push(PathTableMismatch::ExtentMismatch {
+                        path: directory.path.clone(),
+                        path_table: directory.extent,
+                        hierarchy: extent,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        mismatches.extend(
+            hierarchy
+                .into_iter()
+                .map(|(path, extent)| PathTableMismatch::MissingFromPathTable { path, extent }),
+        );
+        Ok(mismatches)
+    }
+
+    /// Lists the sessions of the image, see [`session::sessions`]
+    pub fn sessions(&mut self) -> Result<Vec<Session>, IsoError> {
+        session::sessions(&mut self.reader)
+    }
+
+    /// Returns the underlying data, e.g. to inspect a [`crate::cache::SectorCache`]
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Consumes the reader, returning the underlying data
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// How entries are looked up by path, which every lookup through this reader shares
+    fn lookup(&self) -> PathLookup {
+        PathLookup {
+            root: self.root,
+            path_table: self.path_table,
+            size: self.size,
+            lenient: self.lenient,
+            case_insensitive: self.case_insensitive,
+        }
+    }
+
+    /// Returns a reader over the same image, which reads through the given reader
+    fn with_reader<T: Read + Seek>(&self, reader: T) -> IsoReader<T> {
+        IsoReader {
+            reader,
+            size: self.size,
+
+            volume_descriptors: self.volume_descriptors.clone(),
+            boot_catalogue: self.boot_catalogue.clone(),
+            root: self.root,
+            root_directory: self.root_directory,
+            path_table: self.path_table,
+            lenient: self.lenient,
+            case_insensitive: self.case_insensitive,
+        }
+    }
+}
+
+/// A read-only ISO image that can be shared between threads.
+///
+/// Reads go through [`ReadAt`], so they don't move a shared cursor, and every method only takes
+/// `&self`. Each [`IsoFile`] opened from it has its own position, so many files can be read at
+/// once:
+/// ```no_run
+/// # use std::io::Read;
+/// # use iso9660_rs::reader::SharedIsoReader;
+/// let iso = SharedIsoReader::new(std::fs::File::open("image.iso").unwrap()).unwrap();
+/// std::thread::scope(|scope| {
+///     for path in ["/BOOT/KERNEL", "/BOOT/INITRD"] {
+///         let mut file = iso.open(path).unwrap();
+///         scope.spawn(move || std::io::copy(&mut file, &mut std::io::sink()));
+///     }
+/// });
+/// ```
+#[derive(Debug)]
+pub struct SharedIsoReader<R: ReadAt> {
+    inner: IsoReader<ReadAtCursor<R>>,
+}
+
+impl<R: ReadAt> SharedIsoReader<R> {
+    pub fn new(reader: R) -> Result<Self, IsoError> {
+        Ok(Self {
+            inner: IsoReader::new(ReadAtCursor::new(reader))?,
+        })
+    }
+
+    /// Makes lookups by path ignore ASCII case, see [`IsoReader::case_insensitive`]
+    pub fn case_insensitive(mut self, enabled: bool) -> Self {
+        self.inner = self.inner.case_insensitive(enabled);
+        self
+    }
+
+    /// The size of the underlying image in bytes
+    pub fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    pub fn volume_descriptors(&self) -> &VolumeDescriptorList {
+        self.inner.volume_descriptors()
+    }
+
+    /// The El Torito boot catalogue, if the image has a boot record
+    pub fn boot_catalogue(&self) -> Option<&BootCatalogue> {
+        self.inner.boot_catalogue()
+    }
+
+    /// Returns the directory record of the entry at the given path, see [`IsoReader::stat`]
+    pub fn stat(&self, path: &str) -> Result<DirectoryRecord, IsoError> {
+        let mut reader = ReadAtCursor::new(self.inner.reader.get_ref());
+        self.inner.lookup().stat(&mut reader, path, false)
+    }
+
+    /// Returns the metadata of the entry at the given path, see [`IsoReader::stat`]
+    pub fn metadata(&self, path: &str) -> Result<Metadata, IsoError> {
+        Ok(self.stat(path)?.metadata())
+    }
+
+    /// Returns the directory record of the associated file of the entry at the given path, see
+    /// [`IsoReader::stat_associated`]
+    pub fn stat_associated(&self, path: &str) -> Result<DirectoryRecord, IsoError> {
+        let mut reader = ReadAtCursor::new(self.inner.reader.get_ref());
+        self.inner.lookup().stat(&mut reader, path, true)
+    }
+
+    /// Returns the extended attribute record of the entry at the given path, see
+    /// [`IsoReader::extended_attributes`]
+    pub fn extended_attributes(
+        &self,
+        path: &str,
+    ) -> Result<Option<ExtendedAttributeRecord>, IsoError> {
+        let record = self.stat(path)?;
+        ExtendedAttributeRecord::read(&mut ReadAtCursor::new(self.inner.reader.get_ref()), &record)
+    }
+
+    /// Opens the file at the given path for reading, the file has its own position
+    pub fn open(&self, path: &str) -> Result<IsoFile<ReadAtCursor<&R>>, IsoError> {
+        let record = self.stat(path)?;
+        if record.header.is_directory() {
+            return Err(IsoError::IsADirectory(path.to_string()));
+        }
+        Ok(self.open_record(record))
+    }
+
+    /// Opens the associated file of the entry at the given path for reading, see
+    /// [`IsoReader::stat_associated`]
+    pub fn open_associated(&self, path: &str) -> Result<IsoFile<ReadAtCursor<&R>>, IsoError> {
+        let record = self.stat_associated(path)?;
+        Ok(self.open_record(record))
+    }
+
+    /// Opens the file described by the given record, e.g. one yielded by [`Walk`]
+    pub fn open_record(&self, record: DirectoryRecord) -> IsoFile<ReadAtCursor<&R>> {
+        IsoFile::new(ReadAtCursor::new(self.inner.reader.get_ref()), record)
+    }
+
+    /// Returns a reader over the image with its own position, for the APIs that need one, such
+    /// as directory iteration and [`Walk`]
+    pub fn reader(&self) -> IsoReader<ReadAtCursor<&R>> {
+        self.inner
+            .with_reader(ReadAtCursor::new(self.inner.reader.get_ref()))
+    }
+
+    /// Consumes the reader, returning the underlying data
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner().into_inner()
+    }
+}
+
+/// The state needed to look up entries by path, copied out of an [`IsoReader`] so that lookups
+/// can go through any reader over the image
+#[derive(Debug, Clone, Copy)]
+struct PathLookup {
+    root: DirectoryRecordHeader,
+    path_table: PathTableRef,
+    size: u64,
+    lenient: bool,
+    case_insensitive: bool,
+}
+
+impl PathLookup {
+    /// Looks up the directory record, or the associated file record, at the given path.
+    ///
+    /// In lenient mode, lookups that fail through the path table fall back to reading every
+    /// directory along the path, see [`IsoReader::new_lenient`].
+    fn stat<T: Read + Seek>(
+        self,
+        reader: &mut T,
+        path: &str,
+        associated: bool,
+    ) -> Result<DirectoryRecord, IsoError> {
+        let case_insensitive = self.case_insensitive;
+        match stat(
+            reader,
+            &self.root,
+            self.path_table,
+            path,
+            case_insensitive,
+            associated,
+        ) {
+            Err(_) if self.lenient => recover::stat(
+                reader,
+                &self.root,
+                self.size,
+                path,
+                case_insensitive,
+                associated,
+            ),
+            result => result,
+        }
+    }
+}
+
+/// Looks up the directory record, or the associated file record, at the given path, starting
+/// from the root directory
+fn stat<T: Read + Seek>(
+    reader: &mut T,
+    root: &DirectoryRecordHeader,
+    path_table: PathTableRef,
+    path: &str,
+    case_insensitive: bool,
+    associated: bool,
+) -> Result<DirectoryRecord, IsoError> {
+    let (parent, name) = split_path(path);
+    if name.is_empty() && !associated {
+        return Ok(DirectoryRecord {
+            header: *root,
+            name: IsoStringFile::from_bytes(&[0x00]),
+            continuation: Vec::new(),
+        });
+    }
+    let directory = IsoPathTable {
+        reader: &mut *reader,
+        path_table,
+    }
+    .find_directory_matching(parent, case_insensitive)?
+    .ok_or_else(|| not_found(path))?;
+    IsoDirectory { reader, directory }
+        .find_matching(name, case_insensitive, associated)?
+        .ok_or_else(|| not_found(path))
+}
+
+fn not_found(path: &str) -> IsoError {
+    IsoError::NotFound(path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{path::PathTableKind, tests::format_image};
+
+    #[test]
+    fn test_read_only_slice() {
+        let bytes = format_image(
+            &[("BOOT.BIN", &[0x55; 4096]), ("README.TXT", b"hello")],
+            Some("BOOT.BIN"),
+        );
+        let mut iso = IsoReader::new(Cursor::new(bytes.as_slice())).unwrap();
+        assert_eq!(iso.size(), bytes.len() as u64);
+        assert!(iso.volume_descriptors().boot_record().is_some());
+        assert!(iso.boot_catalogue().unwrap().default_entry().is_valid());
+
+        let names: Vec<String> = iso
+            .root_directory()
+            .entries()
+            .unwrap()
+            .iter()
+            .map(|(_, entry)| entry.name.to_string())
+            .collect();
+        assert!(names.contains(&"README.TXT".to_string()));
+        assert!(names.contains(&"boot.catalog".to_string()));
+        assert_eq!(iso.path_table().entries().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_nested_lookup() {
+        let bytes = format_image(
+            &[
+                ("BOOT/GRUB/GRUB.CFG", b"set timeout=5"),
+                ("BOOT/LIMINE.BIN", &[0xAA; 2048]),
+                ("EFI/BOOT/BOOTX64.EFI", b"MZ"),
+            ],
+            Some("BOOT/LIMINE.BIN"),
+        );
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+
+        let entries = iso.path_table().entries().unwrap();
+        assert_eq!(entries.len(), 5);
+        for entry in &entries[1..] {
+            let parent = &entries[entry.parent_index as usize - 1];
+            assert!(entry.parent_index == 1 || parent.name != "\0");
+        }
+
+        let mut data = Vec::new();
+        iso.open("/EFI/BOOT/BOOTX64.EFI")
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, b"MZ");
+
+        let mut data = String::new();
+        iso.open("BOOT/GRUB/GRUB.CFG")
+            .unwrap()
+            .read_to_string(&mut data)
+            .unwrap();
+        assert_eq!(data, "set timeout=5");
+
+        assert!(iso.stat("/EFI/BOOT").unwrap().header.is_directory());
+        assert!(iso.stat("/").unwrap().header.is_directory());
+        assert_eq!(
+            iso.stat("/BOOT/LIMINE.BIN").unwrap().header.extent.read(),
+            iso.boot_catalogue().unwrap().default_entry().load_rba.get()
+        );
+        assert!(matches!(
+            iso.open("/EFI/BOOT"),
+            Err(IsoError::IsADirectory(_))
+        ));
+        assert!(matches!(
+            iso.stat("/EFI/MISSING"),
+            Err(IsoError::NotFound(_))
+        ));
+        assert!(
+            iso.open_directory("/BOOT/GRUB")
+                .unwrap()
+                .find("GRUB.CFG")
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_path_table_tree() {
+        let bytes = format_image(
+            &[
+                ("BOOT/GRUB/GRUB.CFG", b"set timeout=5"),
+                ("EFI/BOOT/BOOTX64.EFI", b"MZ"),
+            ],
+            None,
+        );
+        let mut iso = IsoReader::new(Cursor::new(bytes.clone())).unwrap();
+
+        let tree = iso.path_table().tree().unwrap();
+        let mut paths: Vec<&str> = tree.directories().iter().map(|d| d.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, ["", "BOOT", "BOOT/GRUB", "EFI", "EFI/BOOT"]);
+
+        let grub = tree.find("/BOOT/GRUB/").unwrap();
+        assert_eq!(tree.get(grub).unwrap().name(), "GRUB");
+        assert_eq!(tree.parent(grub).unwrap().path, "BOOT");
+        assert!(tree.parent(0).is_none());
+        let boot = tree.find("BOOT").unwrap();
+        let children: Vec<&str> = tree.children(boot).map(|(_, d)| d.name()).collect();
+        assert_eq!(children, ["GRUB"]);
+        assert_eq!(
+            tree.get(grub).unwrap().extent as u64,
+            iso.open_directory("/BOOT/GRUB").unwrap().directory.offset
+        );
+        assert!(iso.check_path_table().unwrap().is_empty());
+
+        // Point the first directory after the root at the root directory instead
+        let table = iso.path_table.lpath_table_offset as usize * 2048;
+        let first = &tree.directories()[1];
+        let mut damaged = bytes.clone();
+        damaged[table + 12..table + 16]
+            .copy_from_slice(&tree.directories()[0].extent.to_le_bytes());
+        let mut iso = IsoReader::new(Cursor::new(damaged)).unwrap();
+        assert_eq!(
+            iso.check_path_table().unwrap(),
+            [PathTableMismatch::ExtentMismatch {
+                path: first.path.clone(),
+                path_table: tree.directories()[0].extent,
+                hierarchy: first.extent,
+            }]
+        );
+
+        // A parent directory number that points forward would make a cycle
+        let mut damaged = bytes.clone();
+        damaged[table + 16..table + 18].copy_from_slice(&2u16.to_le_bytes());
+        let mut iso = IsoReader::new(Cursor::new(damaged)).unwrap();
+        assert!(matches!(
+            iso.path_table().tree(),
+            Err(IsoError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_path_tables() {
+        let bytes = format_image(&[("BOOT/GRUB/GRUB.CFG", b"set timeout=5")], None);
+        let mut iso = IsoReader::new(Cursor::new(bytes.clone())).unwrap();
+        iso.path_table().verify().unwrap();
+        assert!(
+            iso.path_table()
+                .read_table(PathTableKind::OptionalTypeM)
+                .unwrap()
+                .is_none()
+        );
+        let root = iso.root.extent.read();
+        let l_table = iso.path_table.lpath_table_offset as usize * 2048;
+        let m_table = iso.path_table.mpath_table_offset as usize * 2048;
+        // The extent of the second entry, after the 10 bytes of the root entry
+        let extent = |table: usize| table + 12..table + 16;
+
+        let verify = |bytes: Vec<u8>| {
+            let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+            iso.path_table().verify()
+        };
+        // Only the corrupt table points at something that isn't a directory
+        let mut damaged = bytes.clone();
+        damaged[extent(m_table)].copy_from_slice(&7u32.to_be_bytes());
+        assert!(matches!(
+            verify(damaged),
+            Err(IsoError::CorruptPathTable {
+                table: PathTableKind::TypeM,
+                entry: 2
+            })
+        ));
+        let mut damaged = bytes.clone();
+        damaged[extent(l_table)].copy_from_slice(&7u32.to_le_bytes());
+        assert!(matches!(
+            verify(damaged),
+            Err(IsoError::CorruptPathTable {
+                table: PathTableKind::TypeL,
+                entry: 2
+            })
+        ));
+
+        // With an optional copy of the type L table, the type M table is outvoted even though it
+        // still points at a directory
+        let mut damaged = bytes.clone();
+        damaged[16 * 2048 + 144..16 * 2048 + 148]
+            .copy_from_slice(&(l_table as u32 / 2048).to_le_bytes());
+        damaged[extent(m_table)].copy_from_slice(&root.to_be_bytes());
+        assert!(matches!(
+            verify(damaged),
+            Err(IsoError::CorruptPathTable {
+                table: PathTableKind::TypeM,
+                entry: 2
+            })
+        ));
+
+        // The name of the second entry isn't valid UTF-8, so the table is compared up to it
+        let mut damaged = bytes.clone();
+        damaged[m_table + 18] = 0xFF;
+        assert!(matches!(
+            verify(damaged),
+            Err(IsoError::CorruptPathTable {
+                table: PathTableKind::TypeM,
+                entry: 2
+            })
+        ));
+
+        // A table past the end of the image can't be read at all
+        let mut damaged = bytes.clone();
+        damaged[16 * 2048 + 148..16 * 2048 + 152].copy_from_slice(&0xFFFFu32.to_be_bytes());
+        assert!(matches!(verify(damaged), Err(IsoError::Io(_))));
+    }
+
+    #[test]
+    fn test_stream_file() {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+        let bytes = format_image(&[("DATA.BIN", &data), ("OTHER.BIN", b"other")], None);
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+
+        let mut file = iso.open("DATA.BIN").unwrap();
+        assert_eq!(file.len(), 5000);
+        let mut copied = Vec::new();
+        assert_eq!(std::io::copy(&mut file, &mut copied).unwrap(), 5000);
+        assert_eq!(copied, data);
+
+        let mut buf = [0u8; 4];
+        assert_eq!(file.seek(SeekFrom::Start(3000)).unwrap(), 3000);
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data[3000..3004]);
+        file.seek(SeekFrom::End(-2)).unwrap();
+        assert_eq!(file.read(&mut buf).unwrap(), 2);
+        assert_eq!(buf[..2], data[4998..]);
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+        assert!(file.seek(SeekFrom::Current(-6000)).is_err());
+
+        assert_eq!(
+            iso.root_directory().read_file("OTHER.BIN").unwrap(),
+            b"other"
+        );
+    }
+
+    #[test]
+    fn test_truncated_file() {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+        let bytes = format_image(&[("DATA.BIN", &data)], None);
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+        let record = iso.stat("DATA.BIN").unwrap();
+
+        // The image ends in the middle of the file
+        let mut bytes = iso.into_inner().into_inner();
+        bytes.truncate(record.extent().offset as usize * 2048 + 1000);
+        let mut file = crate::IsoFile::new(Cursor::new(bytes), record);
+        let mut copied = Vec::new();
+        let err = file.read_to_end(&mut copied).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(copied, data[..1000]);
+    }
+
+    #[test]
+    fn test_file_versions() {
+        let bytes = format_image(
+            &[
+                ("README.TXT;1", b"first"),
+                ("README.TXT;2", b"second"),
+                ("KERNEL.;1", b"kernel"),
+                ("BOOT/GRUB.CFG;1", b"grub"),
+            ],
+            None,
+        );
+        let read = |iso: &mut IsoReader<Cursor<Vec<u8>>>, path: &str| {
+            let mut data = String::new();
+            iso.open(path)?.read_to_string(&mut data)?;
+            Ok::<_, IsoError>(data)
+        };
+        let mut iso = IsoReader::new(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(read(&mut iso, "/README.TXT").unwrap(), "second");
+        assert_eq!(read(&mut iso, "/README.TXT;1").unwrap(), "first");
+        assert_eq!(read(&mut iso, "/KERNEL").unwrap(), "kernel");
+        assert_eq!(read(&mut iso, "/KERNEL.").unwrap(), "kernel");
+        assert_eq!(iso.metadata("/README.TXT").unwrap().version(), Some(2));
+        assert!(matches!(
+            read(&mut iso, "/README.TXT;3"),
+            Err(IsoError::NotFound(_))
+        ));
+        assert!(matches!(
+            read(&mut iso, "/boot/grub.cfg"),
+            Err(IsoError::NotFound(_))
+        ));
+        assert_eq!(
+            iso.root_directory()
+                .find_ignore_case("readme.txt")
+                .unwrap()
+                .unwrap()
+                .version(),
+            Some(2)
+        );
+
+        let mut iso = iso.case_insensitive(true);
+        assert_eq!(read(&mut iso, "/boot/grub.cfg").unwrap(), "grub");
+        assert_eq!(read(&mut iso, "/Readme.Txt;1").unwrap(), "first");
+
+        let slice = crate::slice::IsoSlice::new(&bytes).unwrap();
+        assert_eq!(slice.read("/README.TXT").unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_metadata() {
+        let bytes = format_image(
+            &[("BOOT/KERNEL", b"kernel"), ("README.TXT;1", b"hello")],
+            None,
+        );
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+
+        let readme = iso.metadata("/README.TXT;1").unwrap();
+        assert!(readme.is_file());
+        assert!(!readme.is_hidden());
+        assert!(!readme.is_multi_extent());
+        assert_eq!(readme.len(), 5);
+        assert_eq!(readme.version(), Some(1));
+        assert_eq!(readme.extents().len(), 1);
+        #[cfg(feature = "chrono")]
+        assert!(readme.modified().is_some());
+        assert_eq!(iso.open("/README.TXT;1").unwrap().metadata(), readme);
+
+        let kernel = iso.metadata("/BOOT/KERNEL").unwrap();
+        assert_eq!(kernel.version(), None);
+        let boot = iso.metadata("/BOOT").unwrap();
+        assert!(boot.is_dir());
+        assert_eq!(boot.len(), 2048);
+        assert_eq!(
+            boot.extents()[0],
+            iso.path_table().find_directory("/BOOT").unwrap().unwrap()
+        );
+    }
+
+    static_assertions::assert_impl_all!(SharedIsoReader<std::fs::File>: Send, Sync);
+    static_assertions::assert_impl_all!(IsoFile<ReadAtCursor<&std::fs::File>>: Send, Sync);
+
+    #[test]
+    fn test_shared_reader() {
+        let kernel: Vec<u8> = (0..10000u32).map(|i| (i % 253) as u8).collect();
+        let bytes = format_image(&[("BOOT/KERNEL", &kernel), ("README.TXT", b"hello")], None);
+        let iso = SharedIsoReader::new(bytes).unwrap();
+        assert_eq!(iso.metadata("/BOOT/KERNEL").unwrap().len(), 10000);
+
+        // Interleave reads from two handles, which would clash if they shared a cursor
+        let mut a = iso.open("/BOOT/KERNEL").unwrap();
+        let mut b = iso.open("/README.TXT").unwrap();
+        let mut buf = [0u8; 3];
+        a.read_exact(&mut buf).unwrap();
+        b.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hel");
+        a.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, kernel[3..6]);
+
+        let files = std::thread::scope(|scope| {
+            let handles: Vec<_> = ["/BOOT/KERNEL", "/README.TXT"]
+                .map(|path| {
+                    let mut file = iso.open(path).unwrap();
+                    scope.spawn(move || {
+                        let mut data = Vec::new();
+                        file.read_to_end(&mut data).unwrap();
+                        data
+                    })
+                })
+                .into_iter()
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(files, vec![kernel, b"hello".to_vec()]);
+        assert_eq!(iso.reader().walk().count(), 3);
+    }
+
+    #[test]
+    fn test_directory_spanning_sectors() {
+        let names: Vec<String> = (0..100)
+            .map(|i| format!("FILE_WITH_LONG_NAME_{:03}.TXT", i))
+            .collect();
+        let files: Vec<(&str, &[u8])> = names
+            .iter()
+            .map(|name| (name.as_str(), &b"x"[..]))
+            .collect();
+        let bytes = format_image(&files, None);
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+
+        let mut root = iso.root_directory();
+        let entries = root.entries().unwrap();
+        // The '.' and '..' entries, along with every file
+        assert_eq!(entries.len(), names.len() + 2);
+        for (offset, entry) in &entries {
+            assert!(offset % 2048 + entry.header.len as u64 <= 2048);
+        }
+        assert!(entries.last().unwrap().0 >= 2048);
+        assert!(root.find("FILE_WITH_LONG_NAME_099.TXT").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_invalid_record_length() {
+        let bytes = format_image(&[("A.TXT", b"a"), ("B.TXT", b"b")], None);
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+        let (offset, _) = iso.root_directory().entries().unwrap()[2];
+        let root = iso.stat("/").unwrap().header.extent.read() as u64;
+
+        // Shrink the record so it can no longer hold its file identifier
+        let mut bytes = iso.into_inner().into_inner();
+        bytes[(root * 2048 + offset) as usize] = 34;
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+        let mut root = iso.root_directory();
+        let mut entries = root.iter();
+        assert!(entries.next().unwrap().is_ok());
+        assert!(entries.next().unwrap().is_ok());
+        assert!(matches!(
+            entries.next().unwrap(),
+            Err(IsoError::InvalidData(_))
+        ));
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn test_hostile_images() {
+        let bytes = format_image(&[("BOOT.BIN", &[0x55; 2048])], Some("BOOT.BIN"));
+
+        // The volume descriptor set is never terminated
+        let mut corrupt = bytes.clone();
+        corrupt[17 * 2048 + 1..17 * 2048 + 6].copy_from_slice(b"XXXXX");
+        corrupt[18 * 2048 + 1..18 * 2048 + 6].copy_from_slice(b"XXXXX");
+        assert!(matches!(
+            IsoReader::new(Cursor::new(corrupt)),
+            Err(IsoError::InvalidDescriptor)
+        ));
+
+        // The set is terminated before the primary volume descriptor
+        let mut corrupt = bytes.clone();
+        corrupt[16 * 2048] = 0xFF;
+        assert!(matches!(
+            IsoReader::new(Cursor::new(corrupt)),
+            Err(IsoError::MissingPrimaryDescriptor)
+        ));
+
+        // The manufacturer of the validation entry no longer matches the checksum
+        let iso = IsoReader::new(Cursor::new(bytes.as_slice())).unwrap();
+        let catalogue = iso
+            .volume_descriptors()
+            .boot_record()
+            .unwrap()
+            .catalog_ptr
+            .get() as usize;
+        let mut corrupt = bytes.clone();
+        corrupt[catalogue * 2048 + 4] = b'X';
+        assert!(matches!(
+            IsoReader::new(Cursor::new(corrupt)),
+            Err(IsoError::BadChecksum { .. })
+        ));
+
+        // Truncated images fail to read instead of panicking
+        assert!(matches!(
+            IsoReader::new(Cursor::new(&bytes[..16 * 2048 + 100])),
+            Err(IsoError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,614 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::{
+    IsoPathTable, PathTableRef,
+    boot::BootCatalogue,
+    directory::{DirectoryRecord, DirectoryRecordHeader, DirectoryRef, FileFlags, keep_match},
+    error::IsoError,
+    split_path,
+    types::IsoStringFile,
+    volume::{PrimaryVolumeDescriptor, VolumeDescriptorList},
+    walk::WalkEntry,
+};
+
+/// The number of sectors after LBA 16 that are searched for a primary volume descriptor,
+/// before giving up on the volume descriptor set
+const MAX_DESCRIPTOR_SECTORS: u64 = 64;
+
+/// A problem that was skipped over while reading an image leniently, see
+/// [`crate::reader::IsoReader::new_lenient`]
+#[derive(Debug)]
+pub struct ParseWarning {
+    /// The sector the problem was found in
+    pub sector: u64,
+    pub error: IsoError,
+}
+
+/// The entries recovered from a damaged image, see [`crate::reader::IsoReader::recover`]
+#[derive(Debug, Default)]
+pub struct Recovery {
+    /// Every entry that could be found, with paths relative to the root directory
+    pub entries: Vec<WalkEntry>,
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// Collects the warnings found while reading an image
+#[derive(Debug, Default)]
+pub(crate) struct Warnings(pub(crate) Vec<ParseWarning>);
+
+impl Warnings {
+    pub(crate) fn push(&mut self, sector: u64, error: IsoError) {
+        log::warn!("Skipping damaged sector {}: {}", sector, error);
+        self.0.push(ParseWarning { sector, error });
+    }
+}
+
+/// Reads the sector at the given LBA, which fails if it is past the end of the image
+fn read_sector<T: Read + Seek>(
+    reader: &mut T,
+    lba: u64,
+    buf: &mut [u8; 2048],
+) -> Result<(), IsoError> {
+    reader.seek(SeekFrom::Start(lba * 2048))?;
+    reader.read_exact(buf)?;
+    Ok(())
+}
+
+/// Parses the volume descriptor set, skipping invalid descriptors.
+///
+/// Descriptors without the `CD001` identifier are skipped until the primary volume descriptor
+/// is found, after which they are treated as a missing terminator.
+pub(crate) fn parse_descriptors<T: Read + Seek>(
+    reader: &mut T,
+    warnings: &mut Warnings,
+) -> VolumeDescriptorList {
+    let mut list = VolumeDescriptorList::empty();
+    let mut sector = [0u8; 2048];
+    for lba in 16..16 + MAX_DESCRIPTOR_SECTORS {
+        if let Err(err) = read_sector(reader, lba, &mut sector) {
+            warnings.push(lba, err);
+            return list;
+        }
+        match list.parse_sector(&sector) {
+            Ok(true) => {}
+            Ok(false) => return list,
+            Err(err) => {
+                warnings.push(lba, err);
+                if list.primary().is_some() {
+                    // This is most likely the data after an unterminated set
+                    return list;
+                }
+            }
+        }
+    }
+    warnings.push(
+        16,
+        IsoError::InvalidData("volume descriptor set is not terminated"),
+    );
+    list
+}
+
+/// Parses the boot catalogue, keeping it if only the checksum of the validation entry is wrong
+pub(crate) fn parse_boot_catalogue<T: Read + Seek>(
+    reader: &mut T,
+    lba: u64,
+    warnings: &mut Warnings,
+) -> Option<BootCatalogue> {
+    let catalogue = reader
+        .seek(SeekFrom::Start(lba * 2048))
+        .map_err(IsoError::from)
+        .and_then(|_| BootCatalogue::parse_unchecked(reader));
+    match catalogue {
+        Ok(catalogue) => {
+            if let Err(err) = catalogue.validation().validate() {
+                warnings.push(lba, err);
+            }
+            Some(catalogue)
+        }
+        Err(err) => {
+            warnings.push(lba, err);
+            None
+        }
+    }
+}
+
+/// Returns the header of the `.` record of the directory at the given LBA, or `None` if there
+/// is no directory there
+fn directory_at<T: Read + Seek>(reader: &mut T, lba: u64) -> Option<DirectoryRecordHeader> {
+    let mut sector = [0u8; 2048];
+    read_sector(reader, lba, &mut sector).ok()?;
+    let current = DirectoryRecord::parse(&sector).ok()?;
+    let is_current = current.name.bytes() == b"\x00"
+        && current.header.is_directory()
+        && current.header.extent.read() as u64 == lba;
+    is_current.then_some(current.header)
+}
+
+/// Finds the root directory, trying the primary volume descriptor, then the path table, and
+/// then every sector of the image
+pub(crate) fn find_root<T: Read + Seek>(
+    reader: &mut T,
+    size: u64,
+    pvd: Option<&PrimaryVolumeDescriptor>,
+    warnings: &mut Warnings,
+) -> Result<DirectoryRecordHeader, IsoError> {
+    if let Some(pvd) = pvd {
+        let lba = pvd.dir_record.header.extent.read() as u64;
+        if directory_at(reader, lba).is_some() {
+            return Ok(pvd.dir_record.header);
+        }
+        warnings.push(
+            16,
+            IsoError::InvalidData("root directory record doesn't point to a directory"),
+        );
+
+        // The root directory is always the first entry of the path table
+        let path_table = PathTableRef::new(pvd);
+        let root = IsoPathTable {
+            reader: &mut *reader,
+            path_table,
+        }
+        .entries()
+        .ok()
+        .and_then(|entries| entries.first().map(|entry| entry.extent as u64));
+        if let Some(root) = root.and_then(|lba| directory_at(reader, lba)) {
+            return Ok(root);
+        }
+        warnings.push(
+            path_table.lpath_table_offset,
+            IsoError::InvalidData("path table doesn't point to the root directory"),
+        );
+    }
+
+    // The parent of the root directory is itself, which is unique to the root directory
+    let mut sector = [0u8; 2048];
+    for lba in 17..size / 2048 {
+        if read_sector(reader, lba, &mut sector).is_err() {
+            break;
+        }
+        let Ok(current) = DirectoryRecord::parse(&sector) else {
+            continue;
+        };
+        let parent = DirectoryRecord::parse(&sector[current.header.len as usize..]);
+        let is_root = current.name.bytes() == b"\x00"
+            && current.header.is_directory()
+            && current.header.extent.read() as u64 == lba
+            && parent.is_ok_and(|parent| {
+                parent.name.bytes() == b"\x01" && parent.header.extent.read() as u64 == lba
+            });
+        if is_root {
+            warnings.push(lba, IsoError::MissingPrimaryDescriptor);
+            return Ok(current.header);
+        }
+    }
+    Err(IsoError::MissingPrimaryDescriptor)
+}
+
+/// Reads the records of a directory, skipping the rest of a sector after an invalid record,
+/// and any records with extents past the end of the image.
+///
+/// The `.` and `..` records are not returned.
+pub(crate) fn read_directory<T: Read + Seek>(
+    reader: &mut T,
+    directory: DirectoryRef,
+    image_size: u64,
+    warnings: &mut Warnings,
+) -> Vec<DirectoryRecord> {
+    let mut records: Vec<DirectoryRecord> = Vec::new();
+    // Whether the last record is followed by more records of the same multi-extent file
+    let mut continued = false;
+    let mut sector = [0u8; 2048];
+    for index in 0..directory.size.div_ceil(2048) {
+        let lba = directory.offset + index;
+        if let Err(err) = read_sector(reader, lba, &mut sector) {
+            warnings.push(lba, err);
+            break;
+        }
+
+        let end = (directory.size - index * 2048).min(2048) as usize;
+        let mut offset = 0;
+        while offset < end && sector[offset] != 0 {
+            let record = match DirectoryRecord::parse(&sector[offset..end]) {
+                Ok(record) => record,
+                Err(err) => {
+                    // We can't know where the next record starts, so we skip to the next sector
+                    warnings.push(lba, err);
+                    break;
+                }
+            };
+            offset += record.header.len as usize;
+            if record.name.bytes() == b"\x00" || record.name.bytes() == b"\x01" {
+                continue;
+            }
+            let extent = record.extent();
+            if extent.offset * 2048 + extent.size > image_size {
+                warnings.push(lba, IsoError::ExtentOutOfRange(extent));
+                continue;
+            }
+            let was_continued = core::mem::replace(
+                &mut continued,
+                FileFlags::from_bits_retain(record.header.flags).contains(FileFlags::NOT_FINAL),
+            );
+            match records.last_mut() {
+                Some(last) if was_continued && last.name.bytes() == record.name.bytes() => {
+                    last.continuation.push(extent);
+                }
+                _ => records.push(record),
+            }
+        }
+    }
+    records
+}
+
+/// Looks up the directory record at the given path by reading every directory along it
+pub(crate) fn stat<T: Read + Seek>(
+    reader: &mut T,
+    root: &DirectoryRecordHeader,
+    image_size: u64,
+    path: &str,
+    case_insensitive: bool,
+    associated: bool,
+) -> Result<DirectoryRecord, IsoError> {
+    let mut record = DirectoryRecord {
+        header: *root,
+        name: IsoStringFile::from_bytes(&[0x00]),
+        continuation: Vec::new(),
+    };
+    let (parent, name) = split_path(path);
+    let mut components = parent.split('/').chain([name]).filter(|c| !c.is_empty());
+    let mut next = components.next();
+    while let Some(component) = next {
+        next = components.next();
+        // Only the last component can be an associated file
+        let associated = associated && next.is_none();
+        if !record.header.is_directory() {
+            return Err(IsoError::NotFound(path.to_string()));
+        }
+        let directory = DirectoryRef {
+            offset: record.header.extent.read() as u64,
+            size: record.header.data_len.read() as u64,
+        };
+        let mut found = None;
+        for child in read_directory(reader, directory, image_size, &mut Warnings::default()) {
+            keep_match(&mut found, child, component, case_insensitive, associated);
+        }
+        record = found.ok_or_else(|| IsoError::NotFound(path.to_string()))?;
+    }
+    Ok(record)
+}
+
+/// Finds every entry that can be reached from the root directory, and from the directories in
+/// the path table
+pub(crate) fn recover<T: Read + Seek>(
+    reader: &mut T,
+    root: &DirectoryRecordHeader,
+    path_table: PathTableRef,
+    image_size: u64,
+) -> Recovery {
+    let mut warnings = Warnings::default();
+    let mut entries = Vec::new();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+
+    let root = DirectoryRef {
+        offset: root.extent.read() as u64,
+        size: root.data_len.read() as u64,
+    };
+    visited.insert(root.offset);
+    queue.push_back((String::new(), root, 0));
+    walk(
+        reader,
+        image_size,
+        &mut queue,
+        &mut visited,
+        &mut entries,
+        &mut warnings,
+    );
+
+    // Directories can still be found through the path table if their parent is damaged
+    let table = IsoPathTable {
+        reader: &mut *reader,
+        path_table,
+    }
+    .entries();
+    match table {
+        Ok(table) => {
+            let mut paths: Vec<String> = Vec::with_capacity(table.len());
+            for (index, entry) in table.iter().enumerate() {
+                // Path table indices are 1-based, and parents always come before their children
+                let parent = (entry.parent_index as usize)
+                    .checked_sub(1)
+                    .filter(|&parent| parent < index)
+                    .map(|parent| paths[parent].as_str());
+                let path = match parent {
+                    _ if index == 0 => String::new(),
+                    Some("") | None => entry.name.clone(),
+                    Some(parent) => format!("{}/{}", parent, entry.name),
+                };
+                paths.push(path.clone());
+
+                let lba = entry.extent as u64;
+                if !visited.insert(lba) {
+                    continue;
+                }
+                let Some(header) = directory_at(reader, lba) else {
+                    warnings.push(
+                        lba,
+                        IsoError::InvalidData("path table entry doesn't point to a directory"),
+                    );
+                    continue;
+                };
+                let depth = path.split('/').count();
+                entries.push(WalkEntry {
+                    path: path.clone(),
+                    record: DirectoryRecord {
+                        header,
+                        name: IsoStringFile::from_bytes(entry.name.as_bytes()),
+                        continuation: Vec::new(),
+                    },
+                    depth,
+                });
+                let directory = DirectoryRef {
+                    offset: lba,
+                    size: header.data_len.read() as u64,
+                };
+                queue.push_back((path, directory, depth));
+                walk(
+                    reader,
+                    image_size,
+                    &mut queue,
+                    &mut visited,
+                    &mut entries,
+                    &mut warnings,
+                );
+            }
+        }
+        Err(err) => warnings.push(path_table.lpath_table_offset, err),
+    }
+
+    Recovery {
+        entries,
+        warnings: warnings.0,
+    }
+}
+
+/// Reads every directory in the queue and below it, breadth first
+fn walk<T: Read + Seek>(
+    reader: &mut T,
+    image_size: u64,
+    queue: &mut VecDeque<(String, DirectoryRef, usize)>,
+    visited: &mut HashSet<u64>,
+    entries: &mut Vec<WalkEntry>,
+    warnings: &mut Warnings,
+) {
+    while let Some((path, directory, depth)) = queue.pop_front() {
+        for record in read_directory(reader, directory, image_size, warnings) {
+            let name = record.name.to_string();
+            let path = if path.is_empty() {
+                name
+            } else {
+                format!("{}/{}", path, name)
+            };
+            if record.header.is_directory() {
+                let extent = record.header.extent.read() as u64;
+                if visited.insert(extent) {
+                    let directory = DirectoryRef {
+                        offset: extent,
+                        size: record.header.data_len.read() as u64,
+                    };
+                    queue.push_back((path.clone(), directory, depth + 1));
+                }
+            }
+            entries.push(WalkEntry {
+                path,
+                record,
+                depth: depth + 1,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        file::FileData,
+        reader::IsoReader,
+        tests::{format_image, format_input, stage_files},
+        types::Endian,
+    };
+
+    fn image() -> Vec<u8> {
+        format_image(
+            &[
+                ("BOOT/GRUB/GRUB.CFG", b"set timeout=5"),
+                ("BOOT/LIMINE.BIN", &[0xAA; 2048]),
+                ("README.TXT", b"hello"),
+            ],
+            Some("BOOT/LIMINE.BIN"),
+        )
+    }
+
+    fn paths(recovery: &Recovery) -> Vec<&str> {
+        let mut paths: Vec<&str> = recovery.entries.iter().map(|e| e.path.as_str()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_damaged_descriptors() {
+        let bytes = image();
+        let iso = IsoReader::new(Cursor::new(bytes.as_slice())).unwrap();
+        let catalogue = iso.boot_catalogue().unwrap().default_entry().load_rba.get();
+        let catalogue_lba = iso
+            .volume_descriptors()
+            .boot_record()
+            .unwrap()
+            .catalog_ptr
+            .get() as usize;
+
+        // The boot record and the terminator are damaged, and the catalogue checksum is wrong
+        let mut damaged = bytes.clone();
+        damaged[17 * 2048 + 1] = b'X';
+        damaged[18 * 2048 + 1] = b'X';
+        damaged[catalogue_lba * 2048 + 4] = b'X';
+        assert!(IsoReader::new(Cursor::new(damaged.as_slice())).is_err());
+
+        let (mut iso, warnings) = IsoReader::new_lenient(Cursor::new(damaged)).unwrap();
+        assert!(iso.volume_descriptors().boot_record().is_none());
+        assert!(
+            warnings
+                .iter()
+                .any(|w| w.sector == 17 && matches!(w.error, IsoError::InvalidDescriptor))
+        );
+        assert!(iso.boot_catalogue().is_none());
+        assert_eq!(
+            iso.stat("/BOOT/LIMINE.BIN").unwrap().header.extent.read(),
+            catalogue
+        );
+
+        // Only the checksum is wrong, so the catalogue is still read
+        let mut damaged = bytes.clone();
+        damaged[catalogue_lba * 2048 + 4] = b'X';
+        let (iso, warnings) = IsoReader::new_lenient(Cursor::new(damaged)).unwrap();
+        assert_eq!(
+            iso.boot_catalogue().unwrap().default_entry().load_rba.get(),
+            catalogue
+        );
+        assert!(matches!(
+            warnings.as_slice(),
+            [ParseWarning {
+                error: IsoError::BadChecksum { .. },
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_damaged_root() {
+        let bytes = image();
+
+        // The root directory record points at the volume descriptors
+        let mut damaged = bytes.clone();
+        let root_extent = 16 * 2048 + 156 + 2;
+        damaged[root_extent..root_extent + 8].copy_from_slice(&[16, 0, 0, 0, 0, 0, 0, 16]);
+        let (mut iso, warnings) = IsoReader::new_lenient(Cursor::new(damaged)).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(iso.metadata("/BOOT/GRUB/GRUB.CFG").unwrap().is_file());
+
+        // Without any volume descriptors, the root directory is found by scanning
+        let mut damaged = bytes.clone();
+        damaged[16 * 2048..19 * 2048].fill(0);
+        let (mut iso, _) = IsoReader::new_lenient(Cursor::new(damaged)).unwrap();
+        assert!(iso.volume_descriptors().primary().is_none());
+        assert!(iso.metadata("/BOOT/GRUB/GRUB.CFG").unwrap().is_file());
+        let names: Vec<String> = iso
+            .open_directory("/BOOT/GRUB")
+            .unwrap()
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|(_, record)| record.name.to_str().to_owned())
+            .collect();
+        assert!(names.iter().any(|name| name == "GRUB.CFG"));
+        assert!(matches!(
+            iso.open_directory("/README.TXT"),
+            Err(IsoError::NotFound(_))
+        ));
+        assert!(matches!(
+            iso.stat("/BOOT/MISSING"),
+            Err(IsoError::NotFound(_))
+        ));
+        assert_eq!(
+            paths(&iso.recover()),
+            [
+                "BOOT",
+                "BOOT/GRUB",
+                "BOOT/GRUB/GRUB.CFG",
+                "BOOT/LIMINE.BIN",
+                "README.TXT",
+                "boot.catalog"
+            ]
+        );
+
+        assert!(matches!(
+            IsoReader::new_lenient(Cursor::new(vec![0u8; 64 * 2048])),
+            Err(IsoError::MissingPrimaryDescriptor)
+        ));
+    }
+
+    #[test]
+    fn test_damaged_associated() {
+        let (_root, mut files) = stage_files(&[("GAME", b"data fork")]);
+        files
+            .set_associated("GAME", FileData::Data(b"resource fork".to_vec()))
+            .unwrap();
+
+        // Without any volume descriptors, associated files are found like any other entry
+        let mut damaged = format_input(files, None, Vec::new()).unwrap();
+        damaged[16 * 2048..18 * 2048].fill(0);
+        let (mut iso, _) = IsoReader::new_lenient(Cursor::new(damaged)).unwrap();
+        assert_eq!(iso.metadata("/GAME").unwrap().len(), 9);
+        assert_eq!(iso.stat_associated("/GAME").unwrap().metadata().len(), 13);
+        assert!(matches!(
+            iso.stat_associated("/MISSING"),
+            Err(IsoError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_damaged_directory() {
+        let bytes = image();
+        let mut iso = IsoReader::new(Cursor::new(bytes.as_slice())).unwrap();
+        let root = iso.stat("/").unwrap().header.extent.read() as usize;
+        let (offset, boot) = iso
+            .root_directory()
+            .entries()
+            .unwrap()
+            .into_iter()
+            .find(|(_, record)| record.name.to_str() == "BOOT")
+            .unwrap();
+        let boot = boot.header.extent.read() as usize;
+        let (limine, _) = iso
+            .open_directory("/BOOT")
+            .unwrap()
+            .entries()
+            .unwrap()
+            .into_iter()
+            .find(|(_, record)| record.name.to_str() == "LIMINE.BIN")
+            .unwrap();
+
+        // The BOOT record in the root directory can no longer hold its file identifier, and
+        // LIMINE.BIN points past the end of the image
+        let mut damaged = bytes.clone();
+        damaged[root * 2048 + offset as usize] = 34;
+        let extent = boot * 2048 + limine as usize + 2;
+        damaged[extent..extent + 8].copy_from_slice(&[0xFF, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF]);
+
+        let mut iso = IsoReader::new(Cursor::new(damaged)).unwrap();
+        assert!(iso.walk().any(|entry| entry.is_err()));
+        let recovery = iso.recover();
+        let paths = paths(&recovery);
+        // BOOT can only be found through the path table
+        assert!(paths.contains(&"BOOT"));
+        assert!(paths.contains(&"BOOT/GRUB/GRUB.CFG"));
+        assert!(!paths.contains(&"BOOT/LIMINE.BIN"));
+        assert!(
+            recovery
+                .warnings
+                .iter()
+                .any(|w| w.sector == root as u64 && matches!(w.error, IsoError::InvalidData(_)))
+        );
+        assert!(
+            recovery
+                .warnings
+                .iter()
+                .any(|w| matches!(w.error, IsoError::ExtentOutOfRange(_)))
+        );
+    }
+}
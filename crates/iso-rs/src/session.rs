@@ -0,0 +1,200 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{
+    error::IsoError,
+    volume::{PrimaryVolumeDescriptor, VolumeDescriptorList, VolumeDescriptorType},
+};
+
+/// A session of a multi-session image, see [`sessions`]
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// The LBA the session starts at, its volume descriptor set is 16 sectors later
+    pub start: u64,
+    /// The volume space size of the session, which is the LBA after its last sector, since
+    /// every session records the extents of the whole image
+    pub end: u64,
+    pub volume_descriptors: VolumeDescriptorList,
+}
+
+impl Session {
+    /// Reads the volume descriptor set of the session starting at the given LBA
+    pub fn read<R: Read + Seek>(reader: &mut R, start: u64) -> Result<Self, IsoError> {
+        reader.seek(SeekFrom::Start((start + 16) * 2048))?;
+        let volume_descriptors = VolumeDescriptorList::parse(reader)?;
+        let end = volume_descriptors
+            .primary()
+            .ok_or(IsoError::MissingPrimaryDescriptor)?
+            .volume_space_size
+            .read() as u64;
+        Ok(Self {
+            start,
+            end,
+            volume_descriptors,
+        })
+    }
+}
+
+/// The number of sectors after the end of a session that are scanned for the next one. Writers
+/// leave at most the lead-out and lead-in of a CD between sessions, which is 11400 sectors.
+const MAX_SESSION_GAP: u64 = 16384;
+
+/// Lists the sessions of an image, the first one starts at LBA 0 and the last one is the
+/// latest.
+///
+/// Writers like growisofs and xorriso append a session after the end of the previous one, so
+/// the sectors after the end of each session are scanned for the primary volume descriptor of
+/// the next, up to a gap of 16384 sectors. Writers that also copy the latest volume descriptor
+/// set to LBA 16 hide the older sessions from this scan, they can still be opened with
+/// [`crate::reader::IsoReader::new_session`] if their start is known.
+///
+/// This reads up to 32 MiB after each session, so it is only done on request, opening an image
+/// with [`crate::reader::IsoReader::new`] doesn't look for sessions.
+pub fn sessions<R: Read + Seek>(reader: &mut R) -> Result<Vec<Session>, IsoError> {
+    let size = reader.seek(SeekFrom::End(0))? / 2048;
+    let mut sessions = vec![Session::read(reader, 0)?];
+    loop {
+        let last = sessions.last().unwrap();
+        // A session can't start before the end of the previous one
+        let mut lba = last.end.max(last.start + 1) + 16;
+        let end = size.min(lba + MAX_SESSION_GAP);
+        if lba >= end {
+            break;
+        }
+        reader.seek(SeekFrom::Start(lba * 2048))?;
+        let mut sector = [0; 2048];
+        let mut next = None;
+        while lba < end {
+            reader.read_exact(&mut sector)?;
+            lba += 1;
+            if !is_primary(&sector, lba - 1) {
+                continue;
+            }
+            // A match can be a file that contains an image, rather than a session
+            if let Ok(session) = Session::read(reader, lba - 1 - 16) {
+                next = Some(session);
+                break;
+            }
+            reader.seek(SeekFrom::Start(lba * 2048))?;
+        }
+        match next {
+            Some(session) => sessions.push(session),
+            None => break,
+        }
+    }
+    Ok(sessions)
+}
+
+/// Whether the sector at the given LBA is the primary volume descriptor of a later session,
+/// whose volume space size includes the sector
+fn is_primary(sector: &[u8; 2048], lba: u64) -> bool {
+    if sector[0] != VolumeDescriptorType::PrimaryVolumeDescriptor.to_u8()
+        || &sector[1..6] != b"CD001"
+    {
+        return false;
+    }
+    let pvd: &PrimaryVolumeDescriptor = bytemuck::from_bytes(sector);
+    pvd.volume_space_size.read() as u64 > lba
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        directory::{DirectoryRecord, DirectoryRef, FileFlags},
+        reader::IsoReader,
+        tests::format_image,
+        types::Endian,
+    };
+
+    /// Appends a session to an image of 256 sectors, which only has a root directory with the
+    /// README of the first session and a new file
+    fn append_session(mut image: Vec<u8>) -> Vec<u8> {
+        let mut first = IsoReader::new(Cursor::new(image.clone())).unwrap();
+        let readme = first.stat("/README.TXT").unwrap();
+        let mut volume_descriptors = first.volume_descriptors().clone();
+        image.resize(512 * 2048, 0);
+
+        // The session starts at LBA 256, followed by the directory, path tables, and new data
+        let root = DirectoryRef {
+            offset: 280,
+            size: 2048,
+        };
+        let mut directory = Vec::new();
+        DirectoryRecord::new(&[0x00], root, FileFlags::DIRECTORY)
+            .write(&mut directory)
+            .unwrap();
+        DirectoryRecord::new(&[0x01], root, FileFlags::DIRECTORY)
+            .write(&mut directory)
+            .unwrap();
+        DirectoryRecord::new(
+            b"NEW.TXT;1",
+            DirectoryRef {
+                offset: 290,
+                size: 3,
+            },
+            FileFlags::empty(),
+        )
+        .write(&mut directory)
+        .unwrap();
+        readme.write(&mut directory).unwrap();
+        image[280 * 2048..280 * 2048 + directory.len()].copy_from_slice(&directory);
+        // The path tables only have the root directory
+        image[282 * 2048..282 * 2048 + 10].copy_from_slice(&[1, 0, 24, 1, 0, 0, 1, 0, 0, 0]);
+        image[283 * 2048..283 * 2048 + 10].copy_from_slice(&[1, 0, 0, 0, 1, 24, 0, 1, 0, 0]);
+        image[290 * 2048..290 * 2048 + 3].copy_from_slice(b"new");
+
+        let pvd = volume_descriptors.primary_mut().unwrap();
+        pvd.volume_space_size.write(512);
+        pvd.dir_record.header = DirectoryRecord::new(&[0x00], root, FileFlags::DIRECTORY).header;
+        pvd.path_table_size.write(10);
+        pvd.type_l_path_table.set(282);
+        pvd.type_m_path_table.set(283);
+        let mut descriptors = Vec::new();
+        volume_descriptors.write(&mut descriptors).unwrap();
+        image[272 * 2048..272 * 2048 + descriptors.len()].copy_from_slice(&descriptors);
+        image
+    }
+
+    #[test]
+    fn test_sessions() {
+        let first = format_image(&[("README.TXT", b"readme"), ("OLD.TXT", b"old")], None);
+        assert_eq!(sessions(&mut Cursor::new(first.clone())).unwrap().len(), 1);
+        let mut image = Cursor::new(append_session(first));
+
+        let found = sessions(&mut image).unwrap();
+        let bounds: Vec<_> = found
+            .iter()
+            .map(|session| (session.start, session.end))
+            .collect();
+        assert_eq!(bounds, [(0, 256), (256, 512)]);
+
+        // The first session is opened by default
+        let mut first = IsoReader::new(image.clone()).unwrap();
+        assert!(first.stat("/OLD.TXT").is_ok());
+        assert!(matches!(first.stat("/NEW.TXT"), Err(IsoError::NotFound(_))));
+        assert_eq!(first.sessions().unwrap().len(), 2);
+
+        // The latest session shares the README with the first
+        let mut latest = IsoReader::new_session(image, found[1].start).unwrap();
+        let mut read = Vec::new();
+        latest
+            .open("/NEW.TXT")
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, b"new");
+        read.clear();
+        latest
+            .open("/README.TXT")
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, b"readme");
+        assert!(matches!(
+            latest.stat("/OLD.TXT"),
+            Err(IsoError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,461 @@
+use alloc::{string::ToString, vec::Vec};
+
+use crate::{
+    directory::{
+        DirectoryRecord, DirectoryRecordHeader, DirectoryRef, Interleave, Metadata, keep_match,
+    },
+    error::IsoError,
+    path::PathTableEntryHeader,
+    split_path,
+    types::{Endian, FileIdentifier, IsoStringFile},
+    volume::{
+        BootRecordVolumeDescriptor, PrimaryVolumeDescriptor, VolumeDescriptorHeader,
+        VolumeDescriptorType,
+    },
+};
+
+/// A read-only ISO image over a byte slice, such as a memory-mapped file.
+///
+/// Nothing is copied out of the slice, volume descriptors, directory records and path table
+/// entries are all borrowed from it, so opening an image only touches the sectors that are
+/// looked at:
+/// ```no_run
+/// # use iso9660_rs::slice::IsoSlice;
+/// static IMAGE: &[u8] = &[]; // include_bytes!("image.iso")
+/// let iso = IsoSlice::new(IMAGE).unwrap();
+/// let size = iso.stat("/BOOT/KERNEL").unwrap().header.data_len.read();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct IsoSlice<'a> {
+    data: &'a [u8],
+    primary: &'a PrimaryVolumeDescriptor,
+    boot_record: Option<&'a BootRecordVolumeDescriptor>,
+}
+
+/// A directory record borrowed from an [`IsoSlice`]
+#[derive(Debug, Clone, Copy)]
+pub struct DirectoryRecordRef<'a> {
+    pub header: &'a DirectoryRecordHeader,
+    pub name: &'a [u8],
+}
+
+impl DirectoryRecordRef<'_> {
+    pub fn is_directory(&self) -> bool {
+        self.header.is_directory()
+    }
+
+    /// The extent of the data described by the record, see [`DirectoryRecord::extent`]
+    pub fn extent(&self) -> DirectoryRef {
+        DirectoryRef {
+            offset: self.header.extent.read() as u64 + self.header.extended_attr_record as u64,
+            size: self.header.data_len.read() as u64,
+        }
+    }
+
+    /// The file identifier of the record, see [`DirectoryRecord::identifier`]
+    pub fn identifier(&self) -> Option<FileIdentifier<'_>> {
+        FileIdentifier::from_record(self.name, self.header.is_directory())
+    }
+
+    /// The version number of a file, see [`DirectoryRecord::version`]
+    pub fn version(&self) -> Option<u16> {
+        self.identifier().and_then(|identifier| identifier.version)
+    }
+
+    /// Whether this is the record of the entry with the given name, see
+    /// [`DirectoryRecord::matches`]
+    pub fn matches(&self, name: &str, case_insensitive: bool) -> bool {
+        let query = if self.is_directory() {
+            FileIdentifier::directory(name)
+        } else {
+            FileIdentifier::parse(name)
+        };
+        self.identifier()
+            .is_some_and(|identifier| identifier.matches(&query, case_insensitive))
+    }
+
+    /// Copies the record out of the slice
+    pub fn to_record(&self) -> DirectoryRecord {
+        DirectoryRecord {
+            header: *self.header,
+            name: IsoStringFile::from_bytes(self.name),
+            continuation: Vec::new(),
+        }
+    }
+
+    pub fn metadata(&self) -> Metadata {
+        self.to_record().metadata()
+    }
+}
+
+/// A path table entry borrowed from an [`IsoSlice`], read from the little endian table
+#[derive(Debug, Clone, Copy)]
+pub struct PathTableEntryRef<'a> {
+    pub header: &'a PathTableEntryHeader,
+    pub name: &'a [u8],
+}
+
+impl PathTableEntryRef<'_> {
+    /// The LBA of the directory
+    pub fn extent(&self) -> u32 {
+        u32::from_le_bytes(self.header.extent)
+    }
+
+    /// The 1-based index of the parent directory in the path table
+    pub fn parent_index(&self) -> u16 {
+        u16::from_le_bytes(self.header.parent_directory_number)
+    }
+}
+
+impl<'a> IsoSlice<'a> {
+    /// Parses the volume descriptors of the image, which start at LBA 16
+    pub fn new(data: &'a [u8]) -> Result<Self, IsoError> {
+        let mut primary = None;
+        let mut boot_record = None;
+        let mut lba = 16;
+        loop {
+            let sector = Self::sector_of(data, lba).ok_or(IsoError::InvalidData(
+                "volume descriptor set is not terminated",
+            ))?;
+            let header: &VolumeDescriptorHeader = bytemuck::from_bytes(&sector[..7]);
+            match VolumeDescriptorType::from_u8(header.descriptor_type) {
+                VolumeDescriptorType::VolumeSetTerminator => break,
+                _ if !header.is_valid() => {
+                    return Err(IsoError::InvalidDescriptor);
+                }
+                VolumeDescriptorType::PrimaryVolumeDescriptor => {
+                    primary.get_or_insert(bytemuck::from_bytes(sector));
+                }
+                VolumeDescriptorType::BootRecord => {
+                    boot_record.get_or_insert(bytemuck::from_bytes(sector));
+                }
+                _ => {}
+            }
+            lba += 1;
+        }
+
+        Ok(Self {
+            data,
+            primary: primary.ok_or(IsoError::MissingPrimaryDescriptor)?,
+            boot_record,
+        })
+    }
+
+    fn sector_of(data: &'a [u8], lba: u64) -> Option<&'a [u8]> {
+        let start = usize::try_from(lba.checked_mul(2048)?).ok()?;
+        data.get(start..start.checked_add(2048)?)
+    }
+
+    /// The whole image
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns the sector at the given LBA, or `None` if it is past the end of the image
+    pub fn sector(&self, lba: u64) -> Option<&'a [u8]> {
+        Self::sector_of(self.data, lba)
+    }
+
+    pub fn primary(&self) -> &'a PrimaryVolumeDescriptor {
+        self.primary
+    }
+
+    pub fn boot_record(&self) -> Option<&'a BootRecordVolumeDescriptor> {
+        self.boot_record
+    }
+
+    /// Returns the bytes of the given extent, or an error if it is past the end of the image
+    pub fn extent(&self, extent: DirectoryRef) -> Result<&'a [u8], IsoError> {
+        extent
+            .offset
+            .checked_mul(2048)
+            .and_then(|start| Some(start..start.checked_add(extent.size)?))
+            .and_then(|range| {
+                let range = usize::try_from(range.start).ok()?..usize::try_from(range.end).ok()?;
+                self.data.get(range)
+            })
+            .ok_or(IsoError::ExtentOutOfRange(extent))
+    }
+
+    pub fn root_directory(&self) -> SliceDirectoryEntries<'a> {
+        let root = self.primary.dir_record.header;
+        self.directory(DirectoryRef {
+            offset: root.extent.read() as u64,
+            size: root.data_len.read() as u64,
+        })
+    }
+
+    /// Iterates over the records of the given directory
+    pub fn directory(&self, directory: DirectoryRef) -> SliceDirectoryEntries<'a> {
+        SliceDirectoryEntries {
+            data: self.extent(directory),
+            offset: 0,
+        }
+    }
+
+    /// Iterates over the entries of the little endian path table
+    pub fn path_table(&self) -> SlicePathTable<'a> {
+        let extent = DirectoryRef {
+            offset: self.primary.type_l_path_table.get() as u64,
+            size: self.primary.path_table_size.read() as u64,
+        };
+        SlicePathTable {
+            data: self.extent(extent),
+        }
+    }
+
+    /// Finds the directory at the given path through the path table, see
+    /// [`crate::IsoPathTable::find_directory`]
+    pub fn find_directory(&self, path: &str) -> Result<Option<DirectoryRef>, IsoError> {
+        let Some(entry) = self.path_table().find_path(path)? else {
+            return Ok(None);
+        };
+
+        // The path table doesn't store the size of the directory, so we read it from the '.'
+        // entry, which is always the first record of the directory
+        let offset = entry.extent() as u64;
+        let sector = self
+            .sector(offset)
+            .ok_or(IsoError::ExtentOutOfRange(DirectoryRef {
+                offset,
+                size: 2048,
+            }))?;
+        let (header, _) = DirectoryRecordHeader::split(sector)?;
+        Ok(Some(DirectoryRef {
+            offset,
+            size: header.data_len.read() as u64,
+        }))
+    }
+
+    /// Returns the directory record of the entry at the given path, see
+    /// [`crate::reader::IsoReader::stat`].
+    ///
+    /// The record is copied out of the image, so that the extents of a multi-extent file can be
+    /// merged into it, see [`DirectoryRecord::continuation`].
+    pub fn stat(&self, path: &str) -> Result<DirectoryRecord, IsoError> {
+        let not_found = || IsoError::NotFound(path.to_string());
+        let (parent, name) = split_path(path);
+        if name.is_empty() {
+            return Ok(DirectoryRecord {
+                header: self.primary.dir_record.header,
+                name: IsoStringFile::from_bytes(&[0x00]),
+                continuation: Vec::new(),
+            });
+        }
+        let directory = self.find_directory(parent)?.ok_or_else(not_found)?;
+        let mut found = None;
+        for (_, record) in self.directory(directory).to_records()? {
+            keep_match(&mut found, record, name, false, false);
+        }
+        found.ok_or_else(not_found)
+    }
+
+    /// Returns the contents of the file at the given path, borrowed from the image.
+    ///
+    /// The extents of a multi-extent file have to be contiguous to be borrowed as one slice, and
+    /// interleaved files can't be borrowed at all.
+    pub fn read(&self, path: &str) -> Result<&'a [u8], IsoError> {
+        let record = self.stat(path)?;
+        if record.header.is_directory() {
+            return Err(IsoError::IsADirectory(path.to_string()));
+        }
+        if Interleave::from_header(&record.header).is_some() {
+            return Err(IsoError::InvalidInput(
+                "interleaved files can't be borrowed as one slice",
+            ));
+        }
+        let mut extent = record.extent();
+        for next in &record.continuation {
+            if next.offset * 2048 != extent.offset * 2048 + extent.size {
+                return Err(IsoError::InvalidData(
+                    "extents of a multi-extent file aren't contiguous",
+                ));
+            }
+            extent.size += next.size;
+        }
+        self.extent(extent)
+    }
+}
+
+/// An iterator over the records of a directory in an [`IsoSlice`], along with their offset
+/// from the start of the directory
+pub struct SliceDirectoryEntries<'a> {
+    /// The directory extent, or the error from looking it up
+    data: Result<&'a [u8], IsoError>,
+    offset: usize,
+}
+
+impl<'a> SliceDirectoryEntries<'a> {
+    /// Iterates over the records of a directory extent that was read into memory
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data: Ok(data),
+            offset: 0,
+        }
+    }
+
+    /// Copies every record out of the directory, merging the records of each multi-extent file
+    /// into its first record, see [`DirectoryRecord::continuation`]
+    pub fn to_records(mut self) -> Result<Vec<(u64, DirectoryRecord)>, IsoError> {
+        let mut records = Vec::new();
+        while let Some(entry) = self.next() {
+            let (offset, record) = entry?;
+            let mut record = record.to_record();
+            record.read_continuation(|| {
+                self.next()
+                    .map(|next| next.map(|(_, record)| record.to_record()))
+            })?;
+            records.push((offset, record));
+        }
+        Ok(records)
+    }
+}
+
+impl<'a> Iterator for SliceDirectoryEntries<'a> {
+    type Item = Result<(u64, DirectoryRecordRef<'a>), IsoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = match &self.data {
+            Ok(data) => *data,
+            Err(_) => {
+                let err = core::mem::replace(&mut self.data, Ok(&[]));
+                return err.err().map(Err);
+            }
+        };
+        while self.offset < data.len() {
+            let sector_end = (self.offset / 2048 + 1) * 2048;
+            if data[self.offset] == 0 {
+                // The rest of the sector is padding, so the next record is in the next sector
+                self.offset = sector_end;
+                continue;
+            }
+
+            let offset = self.offset;
+            // Records can't cross a sector boundary
+            let sector = &data[offset..sector_end.min(data.len())];
+            return match DirectoryRecordHeader::split(sector) {
+                Ok((header, name)) => {
+                    self.offset += header.len as usize;
+                    Some(Ok((offset as u64, DirectoryRecordRef { header, name })))
+                }
+                Err(err) => {
+                    // We can't know where the next record starts, so we stop here
+                    self.offset = data.len();
+                    Some(Err(err))
+                }
+            };
+        }
+        None
+    }
+}
+
+/// An iterator over the entries of the path table in an [`IsoSlice`]
+pub struct SlicePathTable<'a> {
+    /// The remaining entries, or the error from looking the table up
+    data: Result<&'a [u8], IsoError>,
+}
+
+impl<'a> SlicePathTable<'a> {
+    /// Iterates over the entries of a path table that was read into memory
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data: Ok(data) }
+    }
+
+    /// Finds the entry of the directory at the given path
+    pub fn find_path(self, path: &str) -> Result<Option<PathTableEntryRef<'a>>, IsoError> {
+        let entries = self.collect::<Result<Vec<_>, _>>()?;
+        // Path table indices are 1-based, and the root directory is always the first entry
+        let mut index = 1;
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            let child = entries.iter().enumerate().skip(1).find(|(_, entry)| {
+                entry.parent_index() as usize == index && entry.name == component.as_bytes()
+            });
+            match child {
+                Some((idx, _)) => index = idx + 1,
+                None => return Ok(None),
+            }
+        }
+        Ok(entries.get(index - 1).copied())
+    }
+}
+
+impl<'a> Iterator for SlicePathTable<'a> {
+    type Item = Result<PathTableEntryRef<'a>, IsoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = match core::mem::replace(&mut self.data, Ok(&[])) {
+            Ok(data) => data,
+            Err(err) => return Some(Err(err)),
+        };
+        if data.first().is_none_or(|len| *len == 0) {
+            return None;
+        }
+        match PathTableEntryHeader::split(data) {
+            Ok((header, name)) => {
+                let size = (size_of::<PathTableEntryHeader>() + name.len() + 1) & !1;
+                self.data = Ok(data.get(size..).unwrap_or_default());
+                Some(Ok(PathTableEntryRef { header, name }))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::format_image;
+
+    #[test]
+    fn test_slice_lookup() {
+        let bytes = format_image(
+            &[
+                ("BOOT/GRUB/GRUB.CFG", b"set timeout=5"),
+                ("BOOT/LIMINE.BIN", &[0xAA; 2048]),
+                ("README.TXT", b"hello"),
+            ],
+            Some("BOOT/LIMINE.BIN"),
+        );
+        let iso = IsoSlice::new(&bytes).unwrap();
+        assert!(iso.boot_record().is_some());
+        assert_eq!(iso.path_table().count(), 3);
+
+        let config = iso.read("/BOOT/GRUB/GRUB.CFG").unwrap();
+        assert_eq!(config, b"set timeout=5");
+        // The contents are borrowed straight from the image
+        assert!(bytes.as_ptr_range().contains(&config.as_ptr()));
+
+        let root: Vec<&[u8]> = iso
+            .root_directory()
+            .map(|entry| entry.unwrap().1.name)
+            .collect();
+        assert!(root.contains(&&b"README.TXT"[..]));
+        assert!(iso.stat("/BOOT").unwrap().metadata().is_dir());
+        assert!(iso.stat("/").unwrap().header.is_directory());
+        assert!(matches!(
+            iso.stat("/BOOT/MISSING"),
+            Err(IsoError::NotFound(_))
+        ));
+        assert!(matches!(iso.read("/BOOT"), Err(IsoError::IsADirectory(_))));
+    }
+
+    #[test]
+    fn test_slice_truncated() {
+        let bytes = format_image(&[("README.TXT", b"hello")], None);
+        assert!(IsoSlice::new(&bytes[..16 * 2048 + 100]).is_err());
+
+        let root = IsoSlice::new(&bytes)
+            .unwrap()
+            .primary()
+            .dir_record
+            .header
+            .extent
+            .read() as usize;
+        let iso = IsoSlice::new(&bytes[..root * 2048]).unwrap();
+        assert!(matches!(
+            iso.root_directory().next().unwrap(),
+            Err(IsoError::ExtentOutOfRange(_))
+        ));
+        assert!(iso.stat("/README.TXT").is_err());
+    }
+}
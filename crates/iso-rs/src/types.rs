@@ -1,7 +1,5 @@
+use alloc::{format, vec::Vec};
 use core::marker::PhantomData;
-use std::time::SystemTime;
-
-use chrono::Datelike;
 
 pub trait Charset: Copy + PartialEq + Eq {
     fn is_valid(chars: &[u8]) -> bool;
@@ -70,6 +68,10 @@ impl<C: Charset, const N: usize> IsoStr<C, N> {
         self.chars.iter().position(|&c| c == b' ').unwrap_or(N)
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub const fn from_bytes_exact(bytes: [u8; N]) -> Self {
         Self {
             chars: bytes,
@@ -78,6 +80,7 @@ impl<C: Charset, const N: usize> IsoStr<C, N> {
     }
 
     // TODO: Error type
+    #[allow(clippy::should_implement_trait, clippy::result_unit_err)]
     pub fn from_str(s: &str) -> Result<Self, ()> {
         let mut chars = [b' '; N];
         if s.len() > N {
@@ -145,7 +148,7 @@ impl<C: Charset> IsoString<C> {
 
     pub fn from_bytes(bytes: &[u8]) -> Self {
         Self {
-            chars: bytes.iter().map(|&c| c).collect(),
+            chars: bytes.to_vec(),
             _marker: PhantomData,
         }
     }
@@ -157,6 +160,10 @@ impl<C: Charset> IsoString<C> {
             .unwrap_or(self.chars.len())
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn bytes(&self) -> &[u8] {
         &self.chars
     }
@@ -186,6 +193,94 @@ impl<C: Charset> core::fmt::Debug for IsoString<C> {
     }
 }
 
+/// A file identifier split into its parts, see ECMA-119 7.5.
+///
+/// File identifiers are a name and an extension separated by `.`, followed by `;` and a version
+/// number, e.g. `README.TXT;1`. The version is optional in practice, and names without an
+/// extension still end with the `.` separator, e.g. `KERNEL.;1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileIdentifier<'a> {
+    /// The name before the `.` separator
+    pub name: &'a str,
+    /// The extension between the `.` and `;` separators, which is empty if there is none
+    pub extension: &'a str,
+    /// The version number after the `;` separator
+    pub version: Option<u16>,
+    /// The identifier without the version and the `.` of a name without an extension
+    stem: &'a str,
+}
+
+impl<'a> FileIdentifier<'a> {
+    /// The versions a file identifier can have, see ECMA-119 7.5.2
+    pub const VERSIONS: core::ops::RangeInclusive<u16> = 1..=32767;
+
+    /// Parses the identifier of a file.
+    ///
+    /// A suffix that isn't a version in [`FileIdentifier::VERSIONS`] is part of the name.
+    pub fn parse(identifier: &'a str) -> Self {
+        let (stem, version) = match identifier.rsplit_once(';') {
+            Some((stem, version)) => match version.parse() {
+                Ok(version) if Self::VERSIONS.contains(&version) => (stem, Some(version)),
+                _ => (identifier, None),
+            },
+            None => (identifier, None),
+        };
+        let stem = stem.strip_suffix('.').unwrap_or(stem);
+        let (name, extension) = stem.rsplit_once('.').unwrap_or((stem, ""));
+        Self {
+            name,
+            extension,
+            version,
+            stem,
+        }
+    }
+
+    /// The identifier of a directory, which has no separators, see ECMA-119 7.6
+    pub fn directory(identifier: &'a str) -> Self {
+        Self {
+            name: identifier,
+            extension: "",
+            version: None,
+            stem: identifier,
+        }
+    }
+
+    /// Parses the identifier of a directory record, returning `None` for the `.` and `..`
+    /// records, and identifiers that aren't valid UTF-8
+    pub fn from_record(identifier: &'a [u8], is_directory: bool) -> Option<Self> {
+        if identifier == b"\x00" || identifier == b"\x01" {
+            return None;
+        }
+        let identifier = core::str::from_utf8(identifier).ok()?;
+        Some(if is_directory {
+            Self::directory(identifier)
+        } else {
+            Self::parse(identifier)
+        })
+    }
+
+    /// The identifier without the version, and without the `.` if there is no extension,
+    /// e.g. `README.TXT` for `README.TXT;1` and `KERNEL` for `KERNEL.;1`
+    pub fn stem(&self) -> &'a str {
+        self.stem
+    }
+
+    /// Whether this identifier names the given one, ignoring the version and the `.` of a name
+    /// without an extension.
+    ///
+    /// If the given identifier has a version, only that version matches.
+    pub fn matches(&self, query: &FileIdentifier, case_insensitive: bool) -> bool {
+        let stem = if case_insensitive {
+            self.stem.eq_ignore_ascii_case(query.stem)
+        } else {
+            self.stem == query.stem
+        };
+        stem && query
+            .version
+            .is_none_or(|version| self.version == Some(version))
+    }
+}
+
 pub type IsoStrA<const N: usize> = IsoStr<CharsetA, N>;
 pub type IsoStrD<const N: usize> = IsoStr<CharsetD, N>;
 pub type IsoStrFile<const N: usize> = IsoStr<CharsetFile, N>;
@@ -198,6 +293,7 @@ pub trait FileInterchange {
 
 /// A level 1 `microsoft` filename,
 /// which comes from the FAT 8.3 standard.
+#[allow(dead_code)]
 pub struct InterchangeL1 {
     basename: IsoStrD<8>,
     extension: IsoStrD<3>,
@@ -208,6 +304,7 @@ impl FileInterchange for InterchangeL1 {
     type Padding = u8;
 }
 
+#[allow(dead_code)]
 pub struct InterchangeL2 {
     path: IsoStrFile<30>,
 }
@@ -218,6 +315,7 @@ impl FileInterchange for InterchangeL2 {
 
 /// A filename, which can be either a level 1 or level 2 filename.
 /// And a padding byte if the filename is odd
+#[allow(dead_code)]
 pub struct Filename<F: FileInterchange> {
     file: F,
     version: u8,
@@ -418,7 +516,7 @@ pub trait Endian {
 
 #[repr(transparent)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
-pub struct U16<E: Endianness> {
+pub struct U16<E> {
     bytes: [u8; 2],
     _marker: PhantomData<E>,
 }
@@ -468,7 +566,7 @@ impl<E: Endianness> core::fmt::UpperHex for U16<E> {
 
 #[repr(transparent)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
-pub struct U32<E: Endianness> {
+pub struct U32<E> {
     bytes: [u8; 4],
     _marker: PhantomData<E>,
 }
@@ -518,7 +616,7 @@ impl<E: Endianness> core::fmt::UpperHex for U32<E> {
 
 #[repr(transparent)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
-pub struct U64<E: Endianness> {
+pub struct U64<E> {
     bytes: [u8; 8],
     _marker: PhantomData<E>,
 }
@@ -606,7 +704,7 @@ pub type U32LsbMsb = LsbMsb<U32<LittleEndian>>;
 pub type U64LsbMsb = LsbMsb<U64<LittleEndian>>;
 
 #[repr(C, packed)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Clone, Copy, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct DecDateTime {
     pub year: IsoStrD<4>,
     pub month: IsoStrD<2>,
@@ -619,7 +717,7 @@ pub struct DecDateTime {
 }
 
 impl core::fmt::Debug for DecDateTime {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("DecDateTime")
             .field(
                 "date",
@@ -638,9 +736,12 @@ impl core::fmt::Debug for DecDateTime {
 }
 
 impl DecDateTime {
+    /// The current time, or an unspecified date if the clock isn't available, which is the case
+    /// without the `std` and `chrono` features
+    #[cfg(all(feature = "std", feature = "chrono"))]
     pub fn now() -> Self {
-        use chrono::{DateTime, Datelike, Timelike, Utc};
-        let now: DateTime<Utc> = SystemTime::now().into();
+        use chrono::{Datelike, Timelike};
+        let now = chrono::Utc::now();
         Self {
             year: IsoStrD::from_str(&now.year().to_string()).unwrap(),
             month: IsoStrD::from_str(&now.month().to_string()).unwrap(),
@@ -652,12 +753,61 @@ impl DecDateTime {
             timezone: 0,
         }
     }
+
+    /// The current time, or an unspecified date if the clock isn't available, which is the case
+    /// without the `std` and `chrono` features
+    #[cfg(not(all(feature = "std", feature = "chrono")))]
+    pub fn now() -> Self {
+        Self::unspecified()
+    }
+
+    /// A date that was not specified, which is all zero digits
+    pub const fn unspecified() -> Self {
+        Self {
+            year: IsoStrD::from_bytes_exact(*b"0000"),
+            month: IsoStrD::from_bytes_exact(*b"00"),
+            day: IsoStrD::from_bytes_exact(*b"00"),
+            hour: IsoStrD::from_bytes_exact(*b"00"),
+            minute: IsoStrD::from_bytes_exact(*b"00"),
+            second: IsoStrD::from_bytes_exact(*b"00"),
+            hundredths: IsoStrD::from_bytes_exact(*b"00"),
+            timezone: 0,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_file_identifier() {
+        let readme = FileIdentifier::parse("README.TXT;1");
+        assert_eq!(
+            (readme.name, readme.extension, readme.version),
+            ("README", "TXT", Some(1))
+        );
+        assert_eq!(readme.stem(), "README.TXT");
+
+        let kernel = FileIdentifier::parse("KERNEL.;2");
+        assert_eq!((kernel.name, kernel.extension), ("KERNEL", ""));
+        assert!(kernel.matches(&FileIdentifier::parse("KERNEL"), false));
+        assert!(kernel.matches(&FileIdentifier::parse("kernel."), true));
+        assert!(!kernel.matches(&FileIdentifier::parse("kernel"), false));
+        assert!(!kernel.matches(&FileIdentifier::parse("KERNEL;1"), false));
+
+        // Only directories keep a `;` in their name
+        assert_eq!(FileIdentifier::parse("A;B").stem(), "A;B");
+        // Versions are limited to 1..=32767
+        assert_eq!(FileIdentifier::parse("A.TXT;32767").version, Some(32767));
+        for identifier in ["A.TXT;0", "A.TXT;32768", "A.TXT;65535"] {
+            let parsed = FileIdentifier::parse(identifier);
+            assert_eq!((parsed.version, parsed.stem()), (None, identifier));
+        }
+        assert_eq!(FileIdentifier::directory("V1.0").stem(), "V1.0");
+        assert!(FileIdentifier::from_record(b"\x00", true).is_none());
+    }
+
     #[test]
     fn test_u16() {
         let mut value = U16::<NativeEndian>::new(0x1234);
@@ -1,11 +1,15 @@
-use std::{
+use alloc::vec::Vec;
+use core::{
     ffi::CStr,
     fmt::Debug,
-    io::{Read, Write},
+    ops::{Deref, DerefMut},
 };
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
 
 use crate::{
-    directory::RootDirectoryEntry,
+    directory::{DirectoryRef, RootDirectoryEntry},
+    error::IsoError,
     types::{
         BigEndian, DecDateTime, Endian, IsoStrA, IsoStrD, LittleEndian, U16LsbMsb, U32, U32LsbMsb,
     },
@@ -48,24 +52,25 @@ impl VolumeDescriptorType {
 pub enum VolumeDescriptor {
     BootRecord(BootRecordVolumeDescriptor),
     Primary(PrimaryVolumeDescriptor),
+    Supplementary(SupplementaryVolumeDescriptor),
+    Enhanced(EnhancedVolumeDescriptor),
+    Partition(VolumePartitionDescriptor),
     End(VolumeDescriptorSetTerminator),
     Unknown(UnknownVolumeDescriptor),
 }
 
 impl VolumeDescriptor {
     pub fn as_bytes(&self) -> &[u8] {
-        match self {
-            VolumeDescriptor::BootRecord(entry) => bytemuck::bytes_of(entry),
-            VolumeDescriptor::Primary(entry) => bytemuck::bytes_of(entry),
-            VolumeDescriptor::End(entry) => bytemuck::bytes_of(entry),
-            VolumeDescriptor::Unknown(entry) => bytemuck::bytes_of(entry),
-        }
+        self.to_bytes()
     }
 
     pub fn to_bytes(&self) -> &[u8] {
         match self {
             VolumeDescriptor::BootRecord(entry) => bytemuck::bytes_of(entry),
             VolumeDescriptor::Primary(entry) => bytemuck::bytes_of(entry),
+            VolumeDescriptor::Supplementary(entry) => bytemuck::bytes_of(entry),
+            VolumeDescriptor::Enhanced(entry) => bytemuck::bytes_of(entry),
+            VolumeDescriptor::Partition(entry) => bytemuck::bytes_of(entry),
             VolumeDescriptor::End(entry) => bytemuck::bytes_of(entry),
             VolumeDescriptor::Unknown(entry) => bytemuck::bytes_of(entry),
         }
@@ -75,21 +80,36 @@ impl VolumeDescriptor {
         match self {
             VolumeDescriptor::BootRecord(entry) => entry.header,
             VolumeDescriptor::Primary(entry) => entry.header,
+            VolumeDescriptor::Supplementary(entry) => entry.header,
+            VolumeDescriptor::Enhanced(entry) => entry.header,
+            VolumeDescriptor::Partition(entry) => entry.header,
             VolumeDescriptor::End(entry) => entry.header,
             VolumeDescriptor::Unknown(entry) => entry.header,
         }
     }
 
-    pub fn new(data: &[u8]) -> Self {
-        assert!(data.len() == 2048);
-        let ty = VolumeDescriptorType::from_u8(data[0]);
-        match ty {
+    pub fn new(data: &[u8; 2048]) -> Self {
+        let header = VolumeDescriptorHeader::from_bytes(&data[0..7]);
+        match VolumeDescriptorType::from_u8(header.descriptor_type) {
             VolumeDescriptorType::BootRecord => {
                 VolumeDescriptor::BootRecord(*bytemuck::from_bytes(data))
             }
             VolumeDescriptorType::PrimaryVolumeDescriptor => {
                 VolumeDescriptor::Primary(*bytemuck::from_bytes(data))
             }
+            // Enhanced volume descriptors share the type of supplementary ones, and are told
+            // apart by their version
+            VolumeDescriptorType::SupplementaryVolumeDescriptor
+                if header.version == EnhancedVolumeDescriptor::VERSION =>
+            {
+                VolumeDescriptor::Enhanced(*bytemuck::from_bytes(data))
+            }
+            VolumeDescriptorType::SupplementaryVolumeDescriptor => {
+                VolumeDescriptor::Supplementary(*bytemuck::from_bytes(data))
+            }
+            VolumeDescriptorType::VolumePartitionDescriptor => {
+                VolumeDescriptor::Partition(*bytemuck::from_bytes(data))
+            }
             VolumeDescriptorType::VolumeSetTerminator => {
                 VolumeDescriptor::End(*bytemuck::from_bytes(data))
             }
@@ -113,46 +133,50 @@ impl VolumeDescriptorList {
     /// Parse the volume descriptor list from the given reader
     ///
     /// The caller should seek to the start of the volume descriptor list, which is usually at LBA 16
-    pub fn parse<T: Read>(reader: &mut T) -> Result<Self, std::io::Error> {
-        let mut descriptors = Vec::new();
+    #[cfg(feature = "std")]
+    pub fn parse<T: Read>(reader: &mut T) -> Result<Self, IsoError> {
+        let mut list = Self::empty();
         let mut buffer = [0u8; 2048];
         loop {
             reader.read_exact(&mut buffer)?;
-            let header = VolumeDescriptorHeader::from_bytes(&buffer[0..7]);
-            let ty = VolumeDescriptorType::from_u8(header.descriptor_type);
-            if let VolumeDescriptorType::VolumeSetTerminator = ty {
+            if !list.parse_sector(&buffer)? {
                 break;
             }
-            if !header.is_valid() {
-                // Invalid, which means either we are at the wrong place, or the writer didn't
-                // write an end record
-                panic!("Invalid volume descriptor header, did you forget to seek to LBA 16?");
-            }
+        }
+        Ok(list)
+    }
 
-            descriptors.push(VolumeDescriptor::new(&buffer));
+    /// Parses the next sector of the volume descriptor set, returning false once the set
+    /// terminator is reached
+    pub fn parse_sector(&mut self, sector: &[u8; 2048]) -> Result<bool, IsoError> {
+        let header = VolumeDescriptorHeader::from_bytes(&sector[0..7]);
+        let ty = VolumeDescriptorType::from_u8(header.descriptor_type);
+        if let VolumeDescriptorType::VolumeSetTerminator = ty {
+            return Ok(false);
+        }
+        if !header.is_valid() {
+            // Invalid, which means either we are at the wrong place, or the writer didn't
+            // write an end record
+            return Err(IsoError::InvalidDescriptor);
         }
 
-        Ok(Self { descriptors })
+        self.descriptors.push(VolumeDescriptor::new(sector));
+        Ok(true)
     }
 
-    pub fn primary(&self) -> &PrimaryVolumeDescriptor {
-        self.descriptors
-            .iter()
-            .find_map(|d| match d {
-                VolumeDescriptor::Primary(d) => Some(d),
-                _ => None,
-            })
-            .expect("Primary volume descriptor not found")
+    /// The primary volume descriptor, which every valid image has
+    pub fn primary(&self) -> Option<&PrimaryVolumeDescriptor> {
+        self.descriptors.iter().find_map(|d| match d {
+            VolumeDescriptor::Primary(d) => Some(d),
+            _ => None,
+        })
     }
 
-    pub fn primary_mut(&mut self) -> &mut PrimaryVolumeDescriptor {
-        self.descriptors
-            .iter_mut()
-            .find_map(|d| match d {
-                VolumeDescriptor::Primary(d) => Some(d),
-                _ => None,
-            })
-            .expect("Primary volume descriptor not found")
+    pub fn primary_mut(&mut self) -> Option<&mut PrimaryVolumeDescriptor> {
+        self.descriptors.iter_mut().find_map(|d| match d {
+            VolumeDescriptor::Primary(d) => Some(d),
+            _ => None,
+        })
     }
 
     pub fn boot_record(&self) -> Option<&BootRecordVolumeDescriptor> {
@@ -163,22 +187,57 @@ impl VolumeDescriptorList {
     }
 
     pub fn boot_record_mut(&mut self) -> Option<&mut BootRecordVolumeDescriptor> {
-        self.descriptors
-            .iter_mut()
-            .find_map(|d| match d {
-                VolumeDescriptor::BootRecord(d) => Some(d),
-                _ => None,
-            })
+        self.descriptors.iter_mut().find_map(|d| match d {
+            VolumeDescriptor::BootRecord(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    /// The supplementary volume descriptors, of which there can be several, e.g. one for Joliet
+    pub fn supplementary(&self) -> impl Iterator<Item = &SupplementaryVolumeDescriptor> {
+        self.descriptors.iter().filter_map(|d| match d {
+            VolumeDescriptor::Supplementary(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    /// The supplementary volume descriptor of the Joliet hierarchy, if the image has one
+    pub fn joliet(&self) -> Option<&SupplementaryVolumeDescriptor> {
+        self.supplementary().find(|d| d.joliet_level().is_some())
+    }
+
+    /// The enhanced volume descriptor of an ISO 9660:1999 image
+    pub fn enhanced(&self) -> Option<&EnhancedVolumeDescriptor> {
+        self.descriptors.iter().find_map(|d| match d {
+            VolumeDescriptor::Enhanced(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    /// The volume partition descriptors, which describe the partitions of the volume
+    pub fn partitions(&self) -> impl Iterator<Item = &VolumePartitionDescriptor> {
+        self.descriptors.iter().filter_map(|d| match d {
+            VolumeDescriptor::Partition(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    pub fn partitions_mut(&mut self) -> impl Iterator<Item = &mut VolumePartitionDescriptor> {
+        self.descriptors.iter_mut().filter_map(|d| match d {
+            VolumeDescriptor::Partition(d) => Some(d),
+            _ => None,
+        })
     }
 
     pub fn push(&mut self, descriptor: VolumeDescriptor) {
         self.descriptors.push(descriptor);
     }
 
-    pub fn write<W: Write>(&self, writer: &mut W) -> Result<usize, std::io::Error> {
+    #[cfg(feature = "std")]
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<usize, IsoError> {
         let mut written = 0;
         for descriptor in &self.descriptors {
-            writer.write_all(&descriptor.to_bytes())?;
+            writer.write_all(descriptor.to_bytes())?;
             written += 2048;
         }
         writer.write_all(VolumeDescriptorSetTerminator::new().to_bytes())?;
@@ -200,7 +259,7 @@ pub struct VolumeDescriptorHeader {
 }
 
 impl Debug for VolumeDescriptorHeader {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("VolumeDescriptorHeader")
             .field(
                 "descriptor_type",
@@ -234,12 +293,12 @@ impl VolumeDescriptorHeader {
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct UnknownVolumeDescriptor {
-    header: VolumeDescriptorHeader,
-    data: [u8; 2041],
+    pub header: VolumeDescriptorHeader,
+    pub data: [u8; 2041],
 }
 
 impl Debug for UnknownVolumeDescriptor {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("UnknownVolumeDescriptor")
             .field("header", &self.header)
             .finish_non_exhaustive()
@@ -286,7 +345,7 @@ pub struct PrimaryVolumeDescriptor {
 }
 
 impl Debug for PrimaryVolumeDescriptor {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("PrimaryVolumeDescriptor")
             .field("header", &self.header)
             .field("system_identifier", &self.system_identifier)
@@ -365,6 +424,260 @@ impl PrimaryVolumeDescriptor {
 unsafe impl bytemuck::Zeroable for PrimaryVolumeDescriptor {}
 unsafe impl bytemuck::Pod for PrimaryVolumeDescriptor {}
 
+bitflags::bitflags! {
+    /// The flags of a supplementary volume descriptor, see ECMA-119 8.5.3
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct VolumeFlags: u8 {
+        /// At least one of the escape sequences isn't registered according to ISO 2375
+        const UNREGISTERED_ESCAPE_SEQUENCES = 0b0000_0001;
+    }
+}
+
+/// A supplementary volume descriptor, which describes another directory hierarchy of the
+/// volume, see ECMA-119 8.5.
+///
+/// The layout is the same as the primary volume descriptor, with flags and escape sequences in
+/// fields that are unused there. The escape sequences select the character set of the
+/// identifiers, e.g. UCS-2 for Joliet, so they are kept as bytes.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SupplementaryVolumeDescriptor {
+    pub header: VolumeDescriptorHeader,
+    pub volume_flags: u8,
+    pub system_identifier: [u8; 32],
+    pub volume_identifier: [u8; 32],
+    pub unused0: [u8; 8],
+    pub volume_space_size: U32LsbMsb,
+    pub escape_sequences: [u8; 32],
+    pub volume_set_size: U16LsbMsb,
+    pub volume_sequence_number: U16LsbMsb,
+    pub logical_block_size: U16LsbMsb,
+    pub path_table_size: U32LsbMsb,
+    pub type_l_path_table: U32<LittleEndian>,
+    pub opt_type_l_path_table: U32<LittleEndian>,
+    pub type_m_path_table: U32<BigEndian>,
+    pub opt_type_m_path_table: U32<BigEndian>,
+    pub dir_record: RootDirectoryEntry,
+    pub volume_set_identifier: [u8; 128],
+    pub publisher_identifier: [u8; 128],
+    pub preparer_identifier: [u8; 128],
+    pub application_identifier: [u8; 128],
+    pub copyright_file_identifier: [u8; 37],
+    pub abstract_file_identifier: [u8; 37],
+    pub bibliographic_file_identifier: [u8; 37],
+    pub creation_date: DecDateTime,
+    pub modification_date: DecDateTime,
+    pub expiration_date: DecDateTime,
+    pub effective_date: DecDateTime,
+    pub file_structure_version: u8,
+    pub unused1: u8,
+    pub app_data: [u8; 512],
+    pub reserved: [u8; 653],
+}
+
+impl Debug for SupplementaryVolumeDescriptor {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SupplementaryVolumeDescriptor")
+            .field("header", &self.header)
+            .field("volume_flags", &self.flags())
+            .field("escape_sequences", &self.escape_sequences())
+            .field("volume_space_size", &self.volume_space_size)
+            .field("logical_block_size", &self.logical_block_size)
+            .field("path_table_size", &self.path_table_size)
+            .field("type_l_path_table", &self.type_l_path_table)
+            .field("type_m_path_table", &self.type_m_path_table)
+            .field("dir_record", &self.dir_record)
+            .field("creation_date", &self.creation_date)
+            .field("modification_date", &self.modification_date)
+            .field("file_structure_version", &self.file_structure_version)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SupplementaryVolumeDescriptor {
+    /// The escape sequences of the three levels of Joliet, which use UCS-2 identifiers
+    pub const JOLIET_ESCAPE_SEQUENCES: [&[u8]; 3] = [b"%/@", b"%/C", b"%/E"];
+
+    /// A supplementary volume descriptor with the given escape sequences, which are limited to
+    /// 32 bytes.
+    ///
+    /// Every identifier is blank, and has to be recorded in the character set the escape
+    /// sequences select. For the Joliet escape sequences they are filled with UCS-2 spaces, and
+    /// otherwise zeroed, since the character set isn't known.
+    pub fn new(sectors: u32, escape_sequences: &[u8]) -> Result<Self, IsoError> {
+        if escape_sequences.len() > 32 {
+            return Err(IsoError::InvalidInput("escape sequences are too long"));
+        }
+        let primary = PrimaryVolumeDescriptor::new(sectors);
+        let mut descriptor: Self = bytemuck::cast(primary);
+        descriptor.header =
+            VolumeDescriptorHeader::new(VolumeDescriptorType::SupplementaryVolumeDescriptor);
+        descriptor.volume_flags = 0;
+        descriptor.escape_sequences = [0; 32];
+        descriptor.escape_sequences[..escape_sequences.len()].copy_from_slice(escape_sequences);
+
+        let joliet = descriptor.joliet_level().is_some();
+        for identifier in descriptor.identifiers_mut() {
+            identifier.fill(0);
+            if joliet {
+                // A trailing odd byte can't hold a character, so it stays zeroed
+                for character in identifier.chunks_exact_mut(2) {
+                    character.copy_from_slice(&[0x00, b' ']);
+                }
+            }
+        }
+        Ok(descriptor)
+    }
+
+    /// Every identifier field, which the primary volume descriptor records in ASCII
+    fn identifiers_mut(&mut self) -> [&mut [u8]; 9] {
+        [
+            &mut self.system_identifier,
+            &mut self.volume_identifier,
+            &mut self.volume_set_identifier,
+            &mut self.publisher_identifier,
+            &mut self.preparer_identifier,
+            &mut self.application_identifier,
+            &mut self.copyright_file_identifier,
+            &mut self.abstract_file_identifier,
+            &mut self.bibliographic_file_identifier,
+        ]
+    }
+
+    pub fn flags(&self) -> VolumeFlags {
+        VolumeFlags::from_bits_retain(self.volume_flags)
+    }
+
+    /// The escape sequences, without the padding at the end of the field
+    pub fn escape_sequences(&self) -> &[u8] {
+        let len = self
+            .escape_sequences
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .map_or(0, |last| last + 1);
+        &self.escape_sequences[..len]
+    }
+
+    /// The Joliet level, from 1 to 3, if the descriptor is for a Joliet hierarchy
+    pub fn joliet_level(&self) -> Option<u8> {
+        let escape_sequences = self.escape_sequences();
+        Self::JOLIET_ESCAPE_SEQUENCES
+            .iter()
+            .position(|sequence| escape_sequences == *sequence)
+            .map(|level| level as u8 + 1)
+    }
+}
+
+unsafe impl bytemuck::Zeroable for SupplementaryVolumeDescriptor {}
+unsafe impl bytemuck::Pod for SupplementaryVolumeDescriptor {}
+
+/// The enhanced volume descriptor of ISO 9660:1999, which relaxes the limits on identifiers
+/// and directory depth.
+///
+/// It has the layout and type of a supplementary volume descriptor, which its fields are read
+/// through, with a version and file structure version of 2.
+#[repr(transparent)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EnhancedVolumeDescriptor(pub SupplementaryVolumeDescriptor);
+
+impl EnhancedVolumeDescriptor {
+    /// The version and file structure version of an enhanced volume descriptor
+    pub const VERSION: u8 = 2;
+
+    /// An enhanced volume descriptor, whose identifiers are recorded like those of the primary
+    /// volume descriptor
+    pub fn new(sectors: u32) -> Self {
+        let mut descriptor: SupplementaryVolumeDescriptor =
+            bytemuck::cast(PrimaryVolumeDescriptor::new(sectors));
+        descriptor.header =
+            VolumeDescriptorHeader::new(VolumeDescriptorType::SupplementaryVolumeDescriptor);
+        descriptor.header.version = Self::VERSION;
+        descriptor.file_structure_version = Self::VERSION;
+        Self(descriptor)
+    }
+}
+
+impl Deref for EnhancedVolumeDescriptor {
+    type Target = SupplementaryVolumeDescriptor;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for EnhancedVolumeDescriptor {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Debug for EnhancedVolumeDescriptor {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("EnhancedVolumeDescriptor")
+            .field(&self.0)
+            .finish()
+    }
+}
+
+/// A volume partition descriptor, which reserves an extent of the volume for another system,
+/// see ECMA-119 8.6
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct VolumePartitionDescriptor {
+    pub header: VolumeDescriptorHeader,
+    pub unused0: u8,
+    pub system_identifier: IsoStrA<32>,
+    pub volume_partition_identifier: IsoStrD<32>,
+    /// The LBA of the partition
+    pub volume_partition_location: U32LsbMsb,
+    /// The size of the partition in sectors
+    pub volume_partition_size: U32LsbMsb,
+    pub system_use: [u8; 1960],
+}
+
+impl VolumePartitionDescriptor {
+    /// A descriptor for the partition with the given identifier, which is limited to 32
+    /// d-characters
+    pub fn new(identifier: &str, location: u32, size: u32) -> Result<Self, IsoError> {
+        Ok(Self {
+            header: VolumeDescriptorHeader::new(VolumeDescriptorType::VolumePartitionDescriptor),
+            unused0: 0,
+            system_identifier: IsoStrA::empty(),
+            volume_partition_identifier: IsoStrD::from_str(identifier)
+                .map_err(|()| IsoError::InvalidName(identifier.into()))?,
+            volume_partition_location: U32LsbMsb::new(location),
+            volume_partition_size: U32LsbMsb::new(size),
+            system_use: [0; 1960],
+        })
+    }
+
+    /// The sectors of the partition
+    pub fn extent(&self) -> DirectoryRef {
+        DirectoryRef {
+            offset: self.volume_partition_location.read() as u64,
+            size: self.volume_partition_size.read() as u64 * 2048,
+        }
+    }
+}
+
+impl Debug for VolumePartitionDescriptor {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("VolumePartitionDescriptor")
+            .field("header", &self.header)
+            .field("system_identifier", &self.system_identifier)
+            .field(
+                "volume_partition_identifier",
+                &self.volume_partition_identifier,
+            )
+            .field("volume_partition_location", &self.volume_partition_location)
+            .field("volume_partition_size", &self.volume_partition_size)
+            .finish_non_exhaustive()
+    }
+}
+
+unsafe impl bytemuck::Zeroable for VolumePartitionDescriptor {}
+unsafe impl bytemuck::Pod for VolumePartitionDescriptor {}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct BootRecordVolumeDescriptor {
@@ -389,7 +702,7 @@ impl BootRecordVolumeDescriptor {
 }
 
 impl Debug for BootRecordVolumeDescriptor {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let system_identifier = CStr::from_bytes_until_nul(&self.boot_system_identifier);
         f.debug_struct("BootRecordVolumeDescriptor")
             .field("header", &self.header)
@@ -426,8 +739,14 @@ impl VolumeDescriptorSetTerminator {
     }
 }
 
+impl Default for VolumeDescriptorSetTerminator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Debug for VolumeDescriptorSetTerminator {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("VolumeDescriptorSetTerminator")
             .field("header", &self.header)
             .finish_non_exhaustive()
@@ -445,8 +764,111 @@ mod tests {
     static_assertions::assert_eq_size!(VolumeDescriptorSetTerminator, [u8; 2048]);
     static_assertions::assert_eq_size!(BootRecordVolumeDescriptor, [u8; 2048]);
     static_assertions::assert_eq_size!(UnknownVolumeDescriptor, [u8; 2048]);
+    static_assertions::assert_eq_size!(SupplementaryVolumeDescriptor, [u8; 2048]);
+    static_assertions::assert_eq_size!(EnhancedVolumeDescriptor, [u8; 2048]);
+    static_assertions::assert_eq_size!(VolumePartitionDescriptor, [u8; 2048]);
 
     static_assertions::assert_eq_align!(PrimaryVolumeDescriptor, u8);
     static_assertions::assert_eq_align!(VolumeDescriptorSetTerminator, u8);
     static_assertions::assert_eq_align!(BootRecordVolumeDescriptor, u8);
+    static_assertions::assert_eq_align!(SupplementaryVolumeDescriptor, u8);
+    static_assertions::assert_eq_align!(EnhancedVolumeDescriptor, u8);
+    static_assertions::assert_eq_align!(VolumePartitionDescriptor, u8);
+
+    #[test]
+    fn test_enhanced_volume_descriptor() {
+        let descriptor = EnhancedVolumeDescriptor::new(64);
+        assert_eq!(
+            descriptor.header.descriptor_type,
+            VolumeDescriptorType::SupplementaryVolumeDescriptor.to_u8()
+        );
+        assert_eq!(descriptor.volume_space_size.read(), 64);
+
+        let bytes: [u8; 2048] = bytemuck::cast(descriptor);
+        let VolumeDescriptor::Enhanced(parsed) = VolumeDescriptor::new(&bytes) else {
+            panic!("not parsed as an enhanced volume descriptor");
+        };
+        assert_eq!(parsed.header.version, EnhancedVolumeDescriptor::VERSION);
+        assert_eq!(
+            parsed.file_structure_version,
+            EnhancedVolumeDescriptor::VERSION
+        );
+        assert_eq!(bytemuck::bytes_of(&parsed), bytes);
+
+        // A supplementary volume descriptor has version 1
+        let supplementary = SupplementaryVolumeDescriptor::new(64, b"").unwrap();
+        let bytes: [u8; 2048] = bytemuck::cast(supplementary);
+        assert!(matches!(
+            VolumeDescriptor::new(&bytes),
+            VolumeDescriptor::Supplementary(_)
+        ));
+    }
+
+    #[test]
+    fn test_supplementary_identifiers() {
+        // Without a known character set, the identifiers of the primary descriptor are zeroed
+        let descriptor = SupplementaryVolumeDescriptor::new(64, b"").unwrap();
+        assert_eq!(descriptor.system_identifier, [0; 32]);
+        assert_eq!(descriptor.volume_identifier, [0; 32]);
+        assert_eq!(descriptor.application_identifier, [0; 128]);
+
+        let descriptor = SupplementaryVolumeDescriptor::new(64, b"%/E").unwrap();
+        assert_eq!(descriptor.joliet_level(), Some(3));
+        let spaces = |len: usize| [0x00, b' '].repeat(len / 2);
+        assert_eq!(descriptor.volume_identifier[..], spaces(32));
+        assert_eq!(descriptor.application_identifier[..], spaces(128));
+        assert_eq!(descriptor.copyright_file_identifier[..36], spaces(36));
+        assert_eq!(descriptor.copyright_file_identifier[36], 0);
+        // The primary descriptor records its identifiers in ASCII
+        let primary = PrimaryVolumeDescriptor::new(64);
+        assert_eq!(primary.volume_identifier.to_str(), "ISOIMAGE");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_volume_descriptor_kinds() {
+        let mut list = VolumeDescriptorList::empty();
+        list.push(VolumeDescriptor::Primary(PrimaryVolumeDescriptor::new(64)));
+        list.push(VolumeDescriptor::Supplementary(
+            SupplementaryVolumeDescriptor::new(64, b"%/E").unwrap(),
+        ));
+        list.push(VolumeDescriptor::Enhanced(EnhancedVolumeDescriptor::new(
+            64,
+        )));
+        list.push(VolumeDescriptor::Partition(
+            VolumePartitionDescriptor::new("VENDOR", 40, 8).unwrap(),
+        ));
+        assert!(SupplementaryVolumeDescriptor::new(64, &[b'%'; 33]).is_err());
+        assert!(matches!(
+            VolumePartitionDescriptor::new("vendor", 40, 8),
+            Err(IsoError::InvalidName(_))
+        ));
+
+        let mut bytes = Vec::new();
+        list.write(&mut bytes).unwrap();
+        let list = VolumeDescriptorList::parse(&mut bytes.as_slice()).unwrap();
+        assert_eq!(list.descriptors.len(), 4);
+
+        let joliet = list.joliet().unwrap();
+        assert_eq!(joliet.joliet_level(), Some(3));
+        assert_eq!(joliet.escape_sequences(), b"%/E");
+        assert!(joliet.flags().is_empty());
+        assert_eq!(joliet.header.version, 1);
+        assert_eq!(list.supplementary().count(), 1);
+
+        let enhanced = list.enhanced().unwrap();
+        assert_eq!(enhanced.header.version, EnhancedVolumeDescriptor::VERSION);
+        assert_eq!(enhanced.file_structure_version, 2);
+        assert_eq!(enhanced.joliet_level(), None);
+
+        let partition = list.partitions().next().unwrap();
+        assert_eq!(partition.volume_partition_identifier.to_str(), "VENDOR");
+        assert_eq!(
+            partition.extent(),
+            DirectoryRef {
+                offset: 40,
+                size: 8 * 2048
+            }
+        );
+    }
 }
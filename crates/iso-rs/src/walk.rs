@@ -0,0 +1,277 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    io::{Read, Seek},
+};
+
+use crate::{
+    IsoDirectory,
+    directory::{DirectoryRecord, DirectoryRef, Metadata},
+    error::IsoError,
+};
+
+/// The order in which [`Walk`] visits entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WalkOrder {
+    /// Every directory is followed by all of its descendants, before its next sibling
+    #[default]
+    DepthFirst,
+    /// Every entry at one depth is visited before any entry at the next depth
+    BreadthFirst,
+}
+
+/// An entry yielded by [`Walk`]
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    /// The path of the entry, relative to the directory the walk started at
+    pub path: String,
+    pub record: DirectoryRecord,
+    /// The depth of the entry, entries in the starting directory have a depth of 1
+    pub depth: usize,
+}
+
+impl WalkEntry {
+    pub fn is_directory(&self) -> bool {
+        self.record.header.is_directory()
+    }
+
+    pub fn metadata(&self) -> Metadata {
+        self.record.metadata()
+    }
+}
+
+type EntryFilter<'a> = Box<dyn FnMut(&WalkEntry) -> bool + 'a>;
+
+/// A recursive iterator over a directory tree, see [`crate::reader::IsoReader::walk`] and
+/// [`IsoDirectory::walk`].
+///
+/// The `.` and `..` entries are never yielded, and each directory extent is only descended into
+/// once, so images with directories that point back up the tree still terminate.
+pub struct Walk<'a, T: Read + Seek> {
+    reader: &'a mut T,
+    order: WalkOrder,
+    max_depth: usize,
+    filter: Option<EntryFilter<'a>>,
+
+    /// The directory the walk starts at, which is read on the first call to `next`
+    root: Option<DirectoryRef>,
+    queue: VecDeque<WalkEntry>,
+    visited: HashSet<u64>,
+}
+
+impl<'a, T: Read + Seek> Walk<'a, T> {
+    pub(crate) fn new(reader: &'a mut T, root: DirectoryRef) -> Self {
+        Self {
+            reader,
+            order: WalkOrder::default(),
+            max_depth: usize::MAX,
+            filter: None,
+
+            root: Some(root),
+            queue: VecDeque::new(),
+            visited: HashSet::new(),
+        }
+    }
+
+    pub fn order(mut self, order: WalkOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// The maximum depth to descend to, a depth of 1 only yields the entries of the starting
+    /// directory
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Only yields entries for which the predicate returns true.
+    ///
+    /// Directories that are filtered out are not descended into.
+    pub fn filter_entry<F: FnMut(&WalkEntry) -> bool + 'a>(mut self, predicate: F) -> Self {
+        self.filter = Some(Box::new(predicate));
+        self
+    }
+
+    /// Reads the children of the given directory, and queues them according to the walk order
+    fn push_children(
+        &mut self,
+        directory: DirectoryRef,
+        path: &str,
+        depth: usize,
+    ) -> Result<(), IsoError> {
+        if depth >= self.max_depth {
+            return Ok(());
+        }
+        if !self.visited.insert(directory.offset) {
+            log::warn!(
+                "Directory {} at extent {} was already visited, skipping",
+                path,
+                directory.offset
+            );
+            return Ok(());
+        }
+
+        let mut children = Vec::new();
+        for entry in (IsoDirectory {
+            reader: &mut *self.reader,
+            directory,
+        })
+        .iter()
+        {
+            let (_offset, record) = entry?;
+            if record.name.bytes() == b"\x00" || record.name.bytes() == b"\x01" {
+                continue;
+            }
+            let path = if path.is_empty() {
+                record.name.to_string()
+            } else {
+                format!("{}/{}", path, record.name)
+            };
+            children.push(WalkEntry {
+                path,
+                record,
+                depth: depth + 1,
+            });
+        }
+
+        match self.order {
+            WalkOrder::DepthFirst => {
+                for child in children.into_iter().rev() {
+                    self.queue.push_front(child);
+                }
+            }
+            WalkOrder::BreadthFirst => self.queue.extend(children),
+        }
+        Ok(())
+    }
+}
+
+impl<T: Read + Seek> Iterator for Walk<'_, T> {
+    type Item = Result<WalkEntry, IsoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take()
+            && let Err(err) = self.push_children(root, "", 0)
+        {
+            return Some(Err(err));
+        }
+
+        loop {
+            let entry = self.queue.pop_front()?;
+            if let Some(filter) = &mut self.filter
+                && !filter(&entry)
+            {
+                continue;
+            }
+            if entry.is_directory() {
+                let directory = DirectoryRef {
+                    offset: entry.record.header.extent.read() as u64,
+                    size: entry.record.header.data_len.read() as u64,
+                };
+                if let Err(err) = self.push_children(directory, &entry.path, entry.depth) {
+                    return Some(Err(err));
+                }
+            }
+            return Some(Ok(entry));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{reader::IsoReader, tests::format_image};
+
+    fn test_image() -> IsoReader<Cursor<Vec<u8>>> {
+        let bytes = format_image(
+            &[
+                ("BOOT/GRUB/GRUB.CFG", b"grub"),
+                ("BOOT/KERNEL", b"kernel"),
+                ("EFI/BOOT/BOOTX64.EFI", b"MZ"),
+                ("README.TXT", b"readme"),
+            ],
+            None,
+        );
+        IsoReader::new(Cursor::new(bytes)).unwrap()
+    }
+
+    /// Collects the paths of the walk, sorted, because the order of siblings depends on the host
+    /// filesystem the image was made from
+    fn paths<T: Read + Seek>(walk: Walk<'_, T>) -> Vec<String> {
+        let mut paths: Vec<String> = walk.map(|entry| entry.unwrap().path).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_walk_orders() {
+        let mut iso = test_image();
+        let entries: Vec<WalkEntry> = iso.walk().map(|entry| entry.unwrap()).collect();
+        assert_eq!(entries.len(), 8);
+        // In depth first order, every entry comes after its parent directory, and every
+        // directory's descendants come directly after it
+        for (idx, entry) in entries.iter().enumerate() {
+            if let Some((parent, _)) = entry.path.rsplit_once('/') {
+                let parent_idx = entries.iter().position(|e| e.path == parent).unwrap();
+                assert!(parent_idx < idx);
+                assert!(
+                    entries[parent_idx..idx]
+                        .iter()
+                        .all(|e| e.path.starts_with(parent))
+                );
+            }
+        }
+
+        let depths: Vec<usize> = iso
+            .walk()
+            .order(WalkOrder::BreadthFirst)
+            .map(|entry| entry.unwrap().depth)
+            .collect();
+        assert!(depths.is_sorted());
+        assert_eq!(depths.len(), 8);
+    }
+
+    #[test]
+    fn test_walk_depth_and_filter() {
+        let mut iso = test_image();
+        let shallow = paths(iso.walk().max_depth(1));
+        assert_eq!(shallow, vec!["BOOT", "EFI", "README.TXT"]);
+
+        let filtered = paths(
+            iso.walk()
+                .filter_entry(|entry| !entry.path.starts_with("BOOT")),
+        );
+        assert_eq!(
+            filtered,
+            vec!["EFI", "EFI/BOOT", "EFI/BOOT/BOOTX64.EFI", "README.TXT"]
+        );
+
+        let subtree = paths(iso.open_directory("/BOOT").unwrap().walk());
+        assert_eq!(subtree, vec!["GRUB", "GRUB/GRUB.CFG", "KERNEL"]);
+    }
+
+    #[test]
+    fn test_walk_cycle() {
+        let mut iso = test_image();
+        let root = iso.stat("/").unwrap().header.extent;
+        let (offset, _) = iso
+            .root_directory()
+            .entries()
+            .unwrap()
+            .into_iter()
+            .find(|(_, entry)| entry.name.to_str() == "EFI")
+            .unwrap();
+
+        // Point the EFI directory back at the root directory
+        let mut bytes = iso.into_inner().into_inner();
+        let extent = (root.read() as u64 * 2048 + offset) as usize + 2;
+        bytes[extent..extent + 8].copy_from_slice(bytemuck::bytes_of(&root));
+        let mut iso = IsoReader::new(Cursor::new(bytes)).unwrap();
+
+        let paths = paths(iso.walk());
+        assert!(paths.contains(&"EFI".to_string()));
+        assert!(!paths.iter().any(|path| path.starts_with("EFI/")));
+    }
+}